@@ -0,0 +1,11 @@
+// Generates the gRPC server from proto/meals.proto. protoc comes from protoc-bin-vendored so that
+// building doesn't depend on one being installed
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+
+    tonic_prost_build::configure()
+        .build_client(false)
+        .compile_protos(&["proto/meals.proto"], &["proto"])?;
+
+    Ok(())
+}
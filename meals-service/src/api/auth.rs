@@ -0,0 +1,272 @@
+use std::{
+    future::{ready, Ready},
+    marker::PhantomData,
+    ops::Deref,
+    sync::Arc
+};
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    http::{StatusCode, header::{self, HeaderMap, HeaderName}},
+    middleware::Next,
+    web::Data,
+    Error,
+    FromRequest,
+    HttpMessage,
+    HttpRequest,
+    HttpResponse,
+    ResponseError
+};
+
+use crate::{
+    repository::{
+        state::{AppState, Tenant},
+        api_keys::{Grant, Role},
+        jwt::is_jwt,
+        tenants::{is_valid_name, DEFAULT_TENANT}
+    },
+    api::v2::{ApiError, error_for}
+};
+
+const API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+
+pub const TENANT: HeaderName = HeaderName::from_static("x-tenant-id");
+
+// API key or JWT from "Authorization: Bearer <key>" or "X-API-Key: <key>"
+pub fn credential(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    bearer
+        .or_else(|| headers.get(&API_KEY).and_then(|value| value.to_str().ok()))
+        .map(str::trim)
+}
+
+// Whether requests need a credential, as configured at startup with AUTH_REQUIRED
+pub fn is_enabled(state: &AppState) -> bool {
+    state.is_auth_required()
+}
+
+// What an API key or a valid JWT grants, or None when the credential isn't accepted
+pub fn grant_for(state: &AppState, credential: &str) -> Option<Grant> {
+    match is_jwt(credential) && state.jwt().is_enabled() {
+        true => state.jwt().verify(credential),
+        false => state.api_keys().authenticate(credential)
+    }
+}
+
+// Tenant a request works on: the one named in X-Tenant-ID, else the one its key or token is bound
+// to, else the default tenant. Keys and tokens that aren't bound to a tenant are bound to the
+// default one. Naming another tenant than the credential's is refused (-13) unless it grants the
+// admin role, and names that aren't valid are refused with -15
+pub fn tenant_for(header: Option<&str>, grant: Option<&Grant>) -> Result<String, i32> {
+    let bound = grant.and_then(|grant| grant.tenant.as_deref());
+    let admin = grant.is_none_or(|grant| grant.role == Role::Admin);
+
+    let name = match (header, bound.unwrap_or(DEFAULT_TENANT)) {
+        (Some(header), bound) if header != bound && !admin => return Err(-13),
+        (Some(header), _) => header,
+        (None, bound) => bound
+    };
+
+    match is_valid_name(name) {
+        true => Ok(name.to_string()),
+        false => Err(-15)
+    }
+}
+
+// Looks up the API key or JWT of the request, if any, and keeps what it grants for the Authorized
+// and CurrentTenant extractors. Unknown keys and invalid tokens are rejected here, before any route runs, while
+// whether a route needs a credential at all is up to the route
+pub async fn authenticate(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, Error> {
+    let state = req.app_data::<Data<AppState>>().cloned();
+
+    if let Some(state) = state.filter(|state| is_enabled(state)) {
+        if let Some(credential) = credential(req.headers()) {
+            match grant_for(&state, credential) {
+                Some(grant) => {
+                    req.extensions_mut().insert(grant);
+                },
+                None => {
+                    let res = AuthError { path: req.path().to_string(), code: -12 }.error_response();
+
+                    return Ok(req.into_response(res))
+                }
+            }
+        }
+    }
+
+    next.call(req).await.map(ServiceResponse::map_into_boxed_body)
+}
+
+// Lowest role allowed to use a route
+pub trait MinimumRole {
+    const ROLE: Role;
+}
+
+pub struct Reader;
+
+impl MinimumRole for Reader {
+    const ROLE: Role = Role::Reader;
+}
+
+pub struct Editor;
+
+impl MinimumRole for Editor {
+    const ROLE: Role = Role::Editor;
+}
+
+pub struct Admin;
+
+impl MinimumRole for Admin {
+    const ROLE: Role = Role::Admin;
+}
+
+// Handler argument that only lets the request through with a role of at least R, answering 401
+// (-12) without a key and 403 (-13) with one whose role is too low. While authentication is
+// disabled every request acts as admin
+pub struct Authorized<R: MinimumRole> {
+    role: Role,
+    minimum: PhantomData<R>
+}
+
+impl<R: MinimumRole> Authorized<R> {
+    pub fn role(&self) -> Role {
+        self.role
+    }
+}
+
+impl<R: MinimumRole> FromRequest for Authorized<R> {
+    type Error = AuthError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let enabled = req.app_data::<Data<AppState>>().is_some_and(|state| is_enabled(state));
+
+        let role = match enabled {
+            true => req.extensions().get::<Grant>().map(|grant| grant.role),
+            false => Some(Role::Admin)
+        };
+
+        let result = match role {
+            Some(role) if role >= R::ROLE => Ok(Authorized { role, minimum: PhantomData }),
+            Some(_) => Err(-13),
+            None => Err(-12)
+        };
+
+        ready(result.map_err(|code| AuthError { path: req.path().to_string(), code }))
+    }
+}
+
+// Handler argument for the dishes and meals of the tenant the request works on, as chosen by
+// tenant_for. Tenants that don't exist are answered with 404 (-5)
+pub struct CurrentTenant(Arc<Tenant>);
+
+impl CurrentTenant {
+    pub fn into_inner(self) -> Arc<Tenant> {
+        self.0
+    }
+}
+
+impl Deref for CurrentTenant {
+    type Target = Tenant;
+
+    fn deref(&self) -> &Tenant {
+        &self.0
+    }
+}
+
+impl FromRequest for CurrentTenant {
+    type Error = AuthError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let header = req.headers().get(&TENANT).map(|value| value.to_str().unwrap_or_default().trim());
+
+        let result = match req.app_data::<Data<AppState>>() {
+            Some(state) => tenant_for(header, req.extensions().get::<Grant>()).and_then(|name| state.get_tenant(&name)).map(CurrentTenant),
+            None => Err(-5)
+        };
+
+        ready(result.map_err(|code| AuthError { path: req.path().to_string(), code }))
+    }
+}
+
+#[derive(Debug)]
+pub struct AuthError {
+    path: String,
+    code: i32
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code)
+    }
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        ApiError::new(self.code).status_code()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut res = error_for(&self.path, self.code);
+
+        if self.code == -12 {
+            res.headers_mut().insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_static("Bearer"));
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::api_keys::{Grant, Role};
+
+    use super::tenant_for;
+
+    fn grant(role: Role, tenant: Option<&str>) -> Grant {
+        Grant { role, tenant: tenant.map(String::from), principal: String::from("key:1") }
+    }
+
+    #[test]
+    fn defaults_to_the_default_tenant() {
+        assert_eq!(tenant_for(None, None), Ok(String::from("default")));
+        assert_eq!(tenant_for(None, Some(&grant(Role::Reader, None))), Ok(String::from("default")));
+    }
+
+    #[test]
+    fn uses_the_tenant_credentials_are_bound_to() {
+        assert_eq!(tenant_for(None, Some(&grant(Role::Editor, Some("team-a")))), Ok(String::from("team-a")));
+        assert_eq!(tenant_for(Some("team-a"), Some(&grant(Role::Reader, Some("team-a")))), Ok(String::from("team-a")));
+        assert_eq!(tenant_for(Some("default"), Some(&grant(Role::Reader, None))), Ok(String::from("default")));
+    }
+
+    #[test]
+    fn only_admins_name_other_tenants() {
+        assert_eq!(tenant_for(Some("team-b"), Some(&grant(Role::Editor, Some("team-a")))), Err(-13));
+        assert_eq!(tenant_for(Some("team-b"), Some(&grant(Role::Reader, None))), Err(-13));
+        assert_eq!(tenant_for(Some("team-b"), Some(&grant(Role::Admin, Some("team-a")))), Ok(String::from("team-b")));
+    }
+
+    #[test]
+    fn anyone_names_tenants_while_auth_is_disabled() {
+        assert_eq!(tenant_for(Some("team-b"), None), Ok(String::from("team-b")));
+    }
+
+    #[test]
+    fn refuses_invalid_names() {
+        let long = "a".repeat(65);
+
+        for name in ["", "Team-A", "team a", "team/a", long.as_str()] {
+            assert_eq!(tenant_for(Some(name), None), Err(-15), "{}", name);
+        }
+
+        // Naming another tenant is refused before the name is checked
+        assert_eq!(tenant_for(Some("Team-A"), Some(&grant(Role::Reader, Some("team-a")))), Err(-13));
+    }
+}
@@ -0,0 +1,274 @@
+use std::{fmt, ops::Deref};
+
+use actix_web::{
+    dev::Payload,
+    error::PayloadError,
+    http::{StatusCode, header::ContentType},
+    web::{Bytes, PayloadConfig},
+    FromRequest,
+    HttpMessage,
+    HttpRequest,
+    HttpResponse,
+    ResponseError
+};
+
+use futures::future::LocalBoxFuture;
+
+use serde::de::DeserializeOwned;
+
+use crate::api::representation::Format;
+
+// Largest request body accepted by POST and PUT handlers
+pub const MAX_BODY_SIZE: usize = 64 * 1024;
+
+pub fn payload_config() -> PayloadConfig {
+    PayloadConfig::new(MAX_BODY_SIZE)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BodyError {
+    UnsupportedMediaType,
+    TooLarge,
+    Invalid
+}
+
+impl BodyError {
+    // Codes returned as the response body by the legacy routes
+    pub fn code(&self) -> i32 {
+        match self {
+            BodyError::UnsupportedMediaType => 0,
+            BodyError::TooLarge => -8,
+            BodyError::Invalid => -1
+        }
+    }
+}
+
+impl fmt::Display for BodyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BodyError::UnsupportedMediaType => write!(f, "Unsupported Content-Type"),
+            BodyError::TooLarge => write!(f, "Request body is larger than {} bytes", MAX_BODY_SIZE),
+            BodyError::Invalid => write!(f, "Request body could not be parsed")
+        }
+    }
+}
+
+impl ResponseError for BodyError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            BodyError::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            BodyError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            BodyError::Invalid => StatusCode::UNPROCESSABLE_ENTITY
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .content_type(ContentType::json())
+            .json(self.code())
+    }
+}
+
+// Request body decoded according to its Content-Type (see Format::from_content_type). The size
+// limit comes from the PayloadConfig registered in api::configure
+pub struct Body<T>(pub T);
+
+impl<T> Body<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Body<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for Body<T> {
+    type Error = BodyError;
+    type Future = LocalBoxFuture<'static, Result<Body<T>, BodyError>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let format = Format::from_content_type(req);
+        let bytes = read_bytes(req, payload);
+
+        Box::pin(async move {
+            let format = format.ok_or(BodyError::UnsupportedMediaType)?;
+
+            format.decode::<T>(&bytes.await?).map(Body).ok_or(BodyError::Invalid)
+        })
+    }
+}
+
+// List of names sent either as an array in any of the structured formats or as plain text with one
+// name per line, in the same layout as query.txt
+pub struct NameList(pub Vec<String>);
+
+impl NameList {
+    pub fn into_inner(self) -> Vec<String> {
+        self.0
+    }
+
+    fn from_text(body: &[u8]) -> Option<NameList> {
+        let text = std::str::from_utf8(body).ok()?;
+
+        let names = text
+            .lines()
+            .map(|line| line.trim())
+            .map(|line| line.strip_suffix("\\n").unwrap_or(line).trim()) // query.txt ends every line with a literal \n
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect();
+
+        Some(NameList(names))
+    }
+}
+
+impl FromRequest for NameList {
+    type Error = BodyError;
+    type Future = LocalBoxFuture<'static, Result<NameList, BodyError>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let text = req.mime_type().ok().flatten().is_some_and(|mime| mime.essence_str() == mime::TEXT_PLAIN.essence_str());
+        let format = Format::from_content_type(req);
+        let bytes = read_bytes(req, payload);
+
+        Box::pin(async move {
+            if text {
+                return NameList::from_text(&bytes.await?).ok_or(BodyError::Invalid)
+            }
+
+            let format = format.ok_or(BodyError::UnsupportedMediaType)?;
+
+            format.decode::<Vec<String>>(&bytes.await?).map(NameList).ok_or(BodyError::Invalid)
+        })
+    }
+}
+
+pub fn read_bytes(req: &HttpRequest, payload: &mut Payload) -> LocalBoxFuture<'static, Result<Bytes, BodyError>> {
+    let bytes = Bytes::from_request(req, payload);
+
+    Box::pin(async move {
+        bytes.await.map_err(|err| match err.as_error::<PayloadError>() {
+            Some(PayloadError::Overflow) => BodyError::TooLarge,
+            _ => BodyError::Invalid
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        test::TestRequest,
+        http::StatusCode,
+        body::to_bytes,
+        FromRequest,
+        ResponseError
+    };
+
+    use super::{Body, BodyError, NameList, payload_config, MAX_BODY_SIZE};
+
+    async fn decode(content_type: &str, body: impl Into<Vec<u8>>) -> Result<Vec<String>, BodyError> {
+        let (req, mut payload) = TestRequest::post()
+            .app_data(payload_config())
+            .insert_header(("Content-Type", content_type))
+            .set_payload(body.into())
+            .to_http_parts();
+
+        Body::<Vec<String>>::from_request(&req, &mut payload).await.map(Body::into_inner)
+    }
+
+    async fn name_list(content_type: &str, body: impl Into<Vec<u8>>) -> Result<Vec<String>, BodyError> {
+        let (req, mut payload) = TestRequest::post()
+            .app_data(payload_config())
+            .insert_header(("Content-Type", content_type))
+            .set_payload(body.into())
+            .to_http_parts();
+
+        NameList::from_request(&req, &mut payload).await.map(NameList::into_inner)
+    }
+
+    fn names() -> Vec<String> {
+        vec![String::from("pasta"), String::from("salad")]
+    }
+
+    #[actix_web::test]
+    async fn accepts_json_with_a_utf_8_charset() {
+        assert_eq!(decode("application/json", r#"["pasta","salad"]"#).await, Ok(names()));
+        assert_eq!(decode("application/json; charset=utf-8", r#"["pasta","salad"]"#).await, Ok(names()));
+        assert_eq!(decode("Application/JSON; Charset=\"UTF-8\"", r#"["pasta","salad"]"#).await, Ok(names()));
+    }
+
+    #[actix_web::test]
+    async fn rejects_other_charsets() {
+        assert_eq!(decode("application/json; charset=latin1", r#"["pasta","salad"]"#).await, Err(BodyError::UnsupportedMediaType));
+    }
+
+    #[actix_web::test]
+    async fn reads_structured_syntax_suffixes() {
+        assert_eq!(decode("application/problem+json", r#"["pasta","salad"]"#).await, Ok(names()));
+        assert_eq!(decode("application/vnd.menu+yaml", "- pasta\n- salad\n").await, Ok(names()));
+    }
+
+    #[actix_web::test]
+    async fn accepts_yaml_and_message_pack() {
+        assert_eq!(decode("application/yaml", "- pasta\n- salad\n").await, Ok(names()));
+        assert_eq!(decode("text/yaml", "[pasta, salad]").await, Ok(names()));
+        assert_eq!(decode("application/msgpack", rmp_serde::to_vec(&names()).unwrap()).await, Ok(names()));
+        assert_eq!(decode("application/x-msgpack; charset=binary", rmp_serde::to_vec(&names()).unwrap()).await, Ok(names()));
+    }
+
+    #[actix_web::test]
+    async fn refuses_plain_text_and_csv_with_415() {
+        let err = decode("text/plain", "pasta\nsalad").await.unwrap_err();
+
+        assert_eq!(err, BodyError::UnsupportedMediaType);
+        assert_eq!(err.status_code(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(to_bytes(err.error_response().into_body()).await.unwrap(), "0");
+
+        assert_eq!(decode("text/csv", "pasta\nsalad").await, Err(BodyError::UnsupportedMediaType));
+    }
+
+    #[actix_web::test]
+    async fn refuses_requests_without_a_content_type() {
+        let (req, mut payload) = TestRequest::post().set_payload(r#"["pasta"]"#).to_http_parts();
+
+        assert_eq!(Body::<Vec<String>>::from_request(&req, &mut payload).await.map(Body::into_inner), Err(BodyError::UnsupportedMediaType));
+    }
+
+    #[actix_web::test]
+    async fn reports_invalid_and_oversized_bodies() {
+        assert_eq!(decode("application/json", "{").await, Err(BodyError::Invalid));
+
+        let large = format!(r#"["{}"]"#, "a".repeat(MAX_BODY_SIZE));
+
+        assert_eq!(decode("application/json", large).await, Err(BodyError::TooLarge));
+    }
+
+    #[actix_web::test]
+    async fn name_lists_read_one_name_per_line() {
+        assert_eq!(name_list("text/plain", "pasta\nsalad\n").await, Ok(names()));
+        assert_eq!(name_list("text/plain; charset=utf-8", "  pasta \r\n\n\nsalad").await, Ok(names()));
+    }
+
+    #[actix_web::test]
+    async fn name_lists_read_the_layout_of_query_txt() {
+        assert_eq!(name_list("text/plain", "pasta\\n\nsalad \\n\n").await, Ok(names()));
+    }
+
+    #[actix_web::test]
+    async fn name_lists_read_arrays_in_structured_formats() {
+        assert_eq!(name_list("application/json", r#"["pasta","salad"]"#).await, Ok(names()));
+        assert_eq!(name_list("application/yaml", "- pasta\n- salad\n").await, Ok(names()));
+        assert_eq!(name_list("application/json", r#"{"name":"pasta"}"#).await, Err(BodyError::Invalid));
+    }
+
+    #[actix_web::test]
+    async fn name_lists_refuse_other_bodies() {
+        assert_eq!(name_list("text/plain", vec![0xff, 0xfe]).await, Err(BodyError::Invalid));
+        assert_eq!(name_list("text/csv", "pasta\nsalad").await, Err(BodyError::UnsupportedMediaType));
+    }
+}
@@ -0,0 +1,114 @@
+use actix_web::{
+    http::header::{self, EntityTag, ETag, Header, IfMatch, IfNoneMatch},
+    HttpRequest,
+    HttpResponse,
+    HttpResponseBuilder
+};
+
+use super::representation::Format;
+
+// Strong ETag for a version of a dish or meal, or of a whole collection for listings, in one
+// format, e.g. "7-json". Each representation has its own tag, since a strong tag promises the
+// same bytes, and responses also vary on Accept
+pub fn etag(version: impl Version, format: Format) -> EntityTag {
+    EntityTag::new_strong(format!("{}-{}", version.tag(), format.token()))
+}
+
+// Anything a response can be tagged with: a single version, or a pair of them for representations
+// that embed resources from another collection, such as meals expanded with their dishes
+pub trait Version: Copy {
+    fn tag(self) -> String;
+}
+
+impl Version for u64 {
+    fn tag(self) -> String {
+        self.to_string()
+    }
+}
+
+impl Version for (u64, u64) {
+    fn tag(self) -> String {
+        format!("{}-{}", self.0, self.1)
+    }
+}
+
+pub fn tagged(mut builder: HttpResponseBuilder, version: impl Version, format: Format) -> HttpResponseBuilder {
+    builder
+        .insert_header(ETag(etag(version, format)))
+        .insert_header((header::VARY, "Accept"));
+
+    builder
+}
+
+// 304 response for reads whose If-None-Match already names the current version in the format asked for
+pub fn not_modified(req: &HttpRequest, version: impl Version, format: Format) -> Option<HttpResponse> {
+    let fresh = match IfNoneMatch::parse(req) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag(version, format))),
+        Err(_) => false
+    };
+
+    match fresh {
+        true => Some(tagged(HttpResponse::NotModified(), version, format).finish()),
+        false => None
+    }
+}
+
+// Precondition for updates built from If-Match, to be checked against the current version of the
+// resource. The tag of any format will do, since all of them name the same version, but weak tags
+// never match. Requests without the header are unconditional
+pub fn if_match(req: &HttpRequest) -> impl Fn(u64) -> bool {
+    let header = match req.headers().contains_key(header::IF_MATCH) {
+        true => IfMatch::parse(req).ok(),
+        false => None
+    };
+
+    move |version| match &header {
+        Some(IfMatch::Items(tags)) => tags.iter().any(|tag| !tag.weak && tag.tag().split('-').next() == Some(version.to_string().as_str())),
+        Some(IfMatch::Any) | None => true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::{header, StatusCode}, test::TestRequest};
+
+    use super::{etag, if_match, not_modified, Format};
+
+    #[test]
+    fn each_format_has_its_own_tag() {
+        assert_eq!(etag(7, Format::Json).to_string(), "\"7-json\"");
+        assert_eq!(etag(7, Format::Yaml).to_string(), "\"7-yaml\"");
+        assert_eq!(etag((7, 3), Format::Csv).to_string(), "\"7-3-csv\"");
+    }
+
+    #[test]
+    fn only_the_tag_of_the_same_format_is_fresh() {
+        let req = TestRequest::default().insert_header((header::IF_NONE_MATCH, "\"7-json\"")).to_http_request();
+
+        assert_eq!(not_modified(&req, 7, Format::Json).map(|res| res.status()), Some(StatusCode::NOT_MODIFIED));
+        assert!(not_modified(&req, 7, Format::Yaml).is_none());
+        assert!(not_modified(&req, 8, Format::Json).is_none());
+    }
+
+    #[test]
+    fn if_match_accepts_the_current_version_in_any_format() {
+        let req = TestRequest::default().insert_header((header::IF_MATCH, "\"7-yaml\"")).to_http_request();
+
+        assert!(if_match(&req)(7));
+        assert!(!if_match(&req)(8));
+        assert!(!if_match(&req)(77));
+    }
+
+    #[test]
+    fn if_match_rejects_weak_tags() {
+        let req = TestRequest::default().insert_header((header::IF_MATCH, "W/\"7-json\"")).to_http_request();
+
+        assert!(!if_match(&req)(7));
+    }
+
+    #[test]
+    fn updates_without_if_match_are_unconditional() {
+        assert!(if_match(&TestRequest::default().to_http_request())(7));
+    }
+}
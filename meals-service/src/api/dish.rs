@@ -1,26 +1,66 @@
 use actix_web::{
     get,
     post,
+    put,
     delete,
-    web::{Path, Header, Data, Payload, BytesMut},
+    web::{Path, Query},
+    HttpRequest,
     HttpResponse,
     Error,
-    http::header::ContentType,
+    http::header::{ContentType, CacheControl, CacheDirective},
 };
 
-use futures::StreamExt;
+use futures::{stream, StreamExt};
 
-use serde::Deserialize;
+use serde::{Serialize, Deserialize};
 
-use crate::repository::state::{AppState, Dish};
+use utoipa::ToSchema;
 
-#[derive(Deserialize, Debug)]
+use crate::{
+    repository::{
+        state::Dish,
+        nutrition_api_client::MultiItemPolicy
+    },
+    api::{
+        path::decode_name,
+        search::{SearchQuery, DishSearchResult},
+        list::{ListParams, parse_list_query, list_response},
+        representation::{Format, reply},
+        body::{Body, NameList},
+        conditional::{tagged, not_modified, if_match},
+        auth::{Authorized, Reader, Editor, CurrentTenant}
+    }
+};
+
+#[derive(Deserialize, Debug, ToSchema)]
 pub struct CreateDishRequest {
-    name: String,
+    pub name: String,
+    /// How to handle names the nutrition API matches to several items
+    #[serde(default)]
+    pub multi_item: MultiItemPolicy,
+    /// Item names expected from the nutrition API. Only read when multi_item is confirm and several
+    /// items are matched
+    #[serde(default)]
+    pub items: Vec<String>
+}
+
+// Nutrition API requests made at once while creating a batch of dishes, and the most names a
+// single batch may contain
+const BATCH_CONCURRENCY: usize = 8;
+const MAX_BATCH_SIZE: usize = 100;
+
+// Outcome for one name of a batch: the ID of the new dish, or the code POST /dishes would have returned
+#[derive(Serialize, Debug, ToSchema)]
+pub struct BatchResult {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<i32>
 }
 
 #[get("/rebuild")] // Delete
-pub async fn rebuild(state: Data<AppState>) -> HttpResponse {
+pub async fn rebuild(state: CurrentTenant) -> HttpResponse {
     let id = state.rebuild();
     
     HttpResponse::Ok()
@@ -28,46 +68,53 @@ pub async fn rebuild(state: Data<AppState>) -> HttpResponse {
         .json(id)
 }
 
+#[utoipa::path(
+    tag = "dishes",
+    params(ListParams),
+    responses(
+        (status = 200, description = "Dishes indexed by ID", body = HashMap<String, Dish>,
+            headers(("X-Total-Count" = usize, description = "Number of dishes matching the filters"), ("ETag" = String, description = "Version of the dish collection"))),
+        (status = 304, description = "The dishes haven't changed since the version given in If-None-Match"),
+        (status = 422, description = "Invalid query parameter (-1)", body = i32)
+    )
+)]
 #[get("/dishes")]
-pub async fn get_dishes(state: Data<AppState>) -> HttpResponse {
-    let dishes = state.get_dishes();
-    
-    HttpResponse::Ok()
-        .content_type(ContentType::json())
-        .json(dishes)
-}
-
-#[post("/dishes")]
-pub async fn create_dish(content_type: Header<ContentType>, mut payload: Payload, state: Data<AppState>) -> Result<HttpResponse, Error> {
-    if content_type.to_string() != String::from("application/json") {
-        return Ok(
-            HttpResponse::UnsupportedMediaType()
-                .content_type(ContentType::json())
-                .json(0)
-        )
-    }
-    
-    let data: CreateDishRequest;
-    let mut body = BytesMut::new();
+pub async fn get_dishes(_auth: Authorized<Reader>, req: HttpRequest, format: Format, state: CurrentTenant) -> HttpResponse {
+    match parse_list_query(req.query_string()) {
+        Ok(options) => {
+            let version = state.get_dishes_version();
 
-    while let Some(chunk) = payload.next().await {
-        let chunk = chunk?;
+            if let Some(res) = not_modified(&req, version, format) {
+                return res
+            }
 
-        body.extend_from_slice(&chunk);
-    }
+            let (total, dishes) = state.get_dishes(&options);
 
-    match serde_json::from_slice::<CreateDishRequest>(&body) {
-        Ok(body) => data = body,
-        _ => {
-            return Ok(
-                HttpResponse::UnprocessableEntity()
-                    .content_type(ContentType::json())
-                    .json(-1)
-            )
+            list_response(tagged(HttpResponse::Ok(), version, format), format, total, &dishes)
+        },
+        Err(err_id) => {
+            HttpResponse::UnprocessableEntity()
+                .content_type(ContentType::json())
+                .json(err_id)
         }
     }
+}
 
-    let dish_id = state.create_dish(data.name.clone()).await;
+#[utoipa::path(
+    tag = "dishes",
+    request_body = CreateDishRequest,
+    responses(
+        (status = 201, description = "ID of the new dish", body = i32),
+        (status = 413, description = "Request body is too large (-8)", body = i32),
+        (status = 415, description = "Content-Type is not JSON, YAML or MessagePack (0)", body = i32),
+        (status = 422, description = "Missing name (-1), dish already exists (-2), name not recognized (-3), nutrition API unreachable (-4), multi-item match not accepted (-7) or dish quota of the tenant reached (-14)", body = i32)
+    )
+)]
+#[post("/dishes")]
+pub async fn create_dish(_auth: Authorized<Editor>, data: Body<CreateDishRequest>, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    let data = data.into_inner();
+
+    let dish_id = state.create_dish(data.name.clone(), &data.multi_item, &data.items).await;
 
     match dish_id {
         Ok(id) => {
@@ -78,8 +125,16 @@ pub async fn create_dish(content_type: Header<ContentType>, mut payload: Payload
             )
         },
         Err(err_id) => {
+            let mut res = HttpResponse::UnprocessableEntity();
+
+            // Not kept for the Idempotency-Key, so a retry creates the dish once the nutrition API
+            // is reachable again
+            if err_id == -4 {
+                res.insert_header(CacheControl(vec![CacheDirective::NoStore]));
+            }
+
             Ok(
-                HttpResponse::UnprocessableEntity()
+                res
                     .content_type(ContentType::json())
                     .json(err_id)
             )
@@ -87,6 +142,87 @@ pub async fn create_dish(content_type: Header<ContentType>, mut payload: Payload
     }
 }
 
+#[utoipa::path(
+    tag = "dishes",
+    request_body(description = "Names of the dishes, as an array or as plain text with one name per line",
+        content((Vec<String> = "application/json"), (String = "text/plain"))),
+    responses(
+        (status = 200, description = "One result per name, in the order given", body = Vec<BatchResult>),
+        (status = 413, description = "Request body is too large (-8)", body = i32),
+        (status = 415, description = "Content-Type is not plain text, JSON, YAML or MessagePack (0)", body = i32),
+        (status = 422, description = "Body is not a list of names, or has none or more than 100 (-1)", body = i32)
+    )
+)]
+#[post("/dishes/batch")]
+pub async fn create_dishes(_auth: Authorized<Editor>, names: NameList, state: CurrentTenant) -> HttpResponse {
+    let names = names.into_inner();
+
+    if names.is_empty() || names.len() > MAX_BATCH_SIZE {
+        return HttpResponse::UnprocessableEntity()
+            .content_type(ContentType::json())
+            .json(-1)
+    }
+
+    let policy = MultiItemPolicy::default();
+    let confirmed = Vec::new();
+
+    let mut results: Vec<(usize, BatchResult)> = stream::iter(names.into_iter().enumerate())
+        .map(|(index, name)| {
+            let (state, policy, confirmed) = (&state, &policy, &confirmed);
+
+            async move {
+                let res = state.create_dish(name.clone(), policy, confirmed).await;
+
+                (index, BatchResult { name, id: res.ok(), error: res.err() })
+            }
+        })
+        .buffer_unordered(BATCH_CONCURRENCY)
+        .collect()
+        .await;
+
+    results.sort_by_key(|(index, _)| *index);
+
+    let results: Vec<BatchResult> = results.into_iter().map(|(_, result)| result).collect();
+
+    let mut res = HttpResponse::Ok();
+
+    // A retry under the same Idempotency-Key runs the whole batch again, creating the dishes the
+    // nutrition API couldn't be reached for and reporting the others as existing (-2)
+    if results.iter().any(|result| result.error == Some(-4)) {
+        res.insert_header(CacheControl(vec![CacheDirective::NoStore]));
+    }
+
+    res
+        .content_type(ContentType::json())
+        .json(results)
+}
+
+#[utoipa::path(
+    tag = "dishes",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Matching dishes, best match first", body = Vec<DishSearchResult>),
+        (status = 422, description = "Missing or empty query (-1)", body = i32)
+    )
+)]
+#[get("/dishes/search")]
+pub async fn search_dishes(_auth: Authorized<Reader>, query: Query<SearchQuery>, format: Format, state: CurrentTenant) -> HttpResponse {
+    match query.get_query() {
+        Some(q) => reply(HttpResponse::Ok(), format, &state.search_dishes(q, query.get_limit())),
+        None => {
+            HttpResponse::UnprocessableEntity()
+                .content_type(ContentType::json())
+                .json(-1)
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "dishes",
+    responses(
+        (status = 405, description = "Deleting every dish is not supported", body = String)
+    )
+)]
 #[delete("/dishes")]
 pub async fn delete_dishes() -> HttpResponse {
     HttpResponse::MethodNotAllowed()
@@ -94,9 +230,18 @@ pub async fn delete_dishes() -> HttpResponse {
         .json(String::from("Not implemented"))
 }
 
+#[utoipa::path(
+    tag = "dishes",
+    params(("name" = String, Path, description = "ID of the dish, or its name if it doesn't parse as an integer")),
+    responses(
+        (status = 200, description = "The dish", body = Dish, headers(("ETag" = String, description = "Version of the dish"))),
+        (status = 304, description = "The dish hasn't changed since the version given in If-None-Match"),
+        (status = 404, description = "Dish not found (-5)", body = i32)
+    )
+)]
 #[get("/dishes/{name}")]
-pub async fn get_dish(path: Path<String>, state: Data<AppState>) -> Result<HttpResponse, Error> {
-    let name = path.into_inner();
+pub async fn get_dish(_auth: Authorized<Reader>, req: HttpRequest, path: Path<String>, format: Format, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    let name = decode_name(path.into_inner());
 
     let dish: Result<Dish, i32>;
 
@@ -105,13 +250,45 @@ pub async fn get_dish(path: Path<String>, state: Data<AppState>) -> Result<HttpR
         Err(_) => dish = state.get_dish_by_name(name)
     }
 
+    dish_response(&req, format, dish)
+}
+
+#[utoipa::path(
+    tag = "dishes",
+    params(("id" = i32, Path, description = "ID of the dish")),
+    responses(
+        (status = 200, description = "The dish", body = Dish, headers(("ETag" = String, description = "Version of the dish"))),
+        (status = 304, description = "The dish hasn't changed since the version given in If-None-Match"),
+        (status = 404, description = "Dish not found (-5)", body = i32)
+    )
+)]
+#[get("/dishes/id/{id}")]
+pub async fn get_dish_with_id(_auth: Authorized<Reader>, req: HttpRequest, path: Path<i32>, format: Format, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    dish_response(&req, format, state.get_dish_by_id(path.into_inner()))
+}
+
+#[utoipa::path(
+    tag = "dishes",
+    params(("name" = String, Path, description = "Name of the dish, percent-encoded")),
+    responses(
+        (status = 200, description = "The dish", body = Dish, headers(("ETag" = String, description = "Version of the dish"))),
+        (status = 304, description = "The dish hasn't changed since the version given in If-None-Match"),
+        (status = 404, description = "Dish not found (-5)", body = i32)
+    )
+)]
+#[get("/dishes/name/{name:.*}")]
+pub async fn get_dish_with_name(_auth: Authorized<Reader>, req: HttpRequest, path: Path<String>, format: Format, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    dish_response(&req, format, state.get_dish_by_name(decode_name(path.into_inner())))
+}
+
+fn dish_response(req: &HttpRequest, format: Format, dish: Result<Dish, i32>) -> Result<HttpResponse, Error> {
     match dish {
         Ok(data) => {
-            return Ok(
-                HttpResponse::Ok()
-                    .content_type(ContentType::json())
-                    .json(data)
-            )
+            if let Some(res) = not_modified(req, data.version(), format) {
+                return Ok(res)
+            }
+
+            return Ok(reply(tagged(HttpResponse::Ok(), data.version(), format), format, &data))
         },
         Err(_) => {
             return Ok(
@@ -123,9 +300,17 @@ pub async fn get_dish(path: Path<String>, state: Data<AppState>) -> Result<HttpR
     }
 }
 
+#[utoipa::path(
+    tag = "dishes",
+    params(("name" = String, Path, description = "ID of the dish, or its name if it doesn't parse as an integer")),
+    responses(
+        (status = 200, description = "ID of the deleted dish", body = i32),
+        (status = 404, description = "Dish not found (-5)", body = i32)
+    )
+)]
 #[delete("/dishes/{name}")]
-pub async fn delete_dish(path: Path<String>, state: Data<AppState>) -> Result<HttpResponse, Error> {
-    let name = path.into_inner();
+pub async fn delete_dish(_auth: Authorized<Editor>, path: Path<String>, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    let name = decode_name(path.into_inner());
 
     match name.parse::<i32>() {
         Ok(id) => return delete_dish_by_id(id, state).await,
@@ -133,7 +318,33 @@ pub async fn delete_dish(path: Path<String>, state: Data<AppState>) -> Result<Ht
     }
 }
 
-async fn delete_dish_by_id(dish_id: i32, state: Data<AppState>) -> Result<HttpResponse, Error> {
+#[utoipa::path(
+    tag = "dishes",
+    params(("id" = i32, Path, description = "ID of the dish")),
+    responses(
+        (status = 200, description = "ID of the deleted dish", body = i32),
+        (status = 404, description = "Dish not found (-5)", body = i32)
+    )
+)]
+#[delete("/dishes/id/{id}")]
+pub async fn delete_dish_with_id(_auth: Authorized<Editor>, path: Path<i32>, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    delete_dish_by_id(path.into_inner(), state).await
+}
+
+#[utoipa::path(
+    tag = "dishes",
+    params(("name" = String, Path, description = "Name of the dish, percent-encoded")),
+    responses(
+        (status = 200, description = "ID of the deleted dish", body = i32),
+        (status = 404, description = "Dish not found (-5)", body = i32)
+    )
+)]
+#[delete("/dishes/name/{name:.*}")]
+pub async fn delete_dish_with_name(_auth: Authorized<Editor>, path: Path<String>, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    delete_dish_by_name(decode_name(path.into_inner()), state).await
+}
+
+async fn delete_dish_by_id(dish_id: i32, state: CurrentTenant) -> Result<HttpResponse, Error> {
     let res = state.delete_dish_by_id(dish_id);
 
     match res {
@@ -154,7 +365,7 @@ async fn delete_dish_by_id(dish_id: i32, state: Data<AppState>) -> Result<HttpRe
     }
 }
 
-async fn delete_dish_by_name(name: String, state: Data<AppState>) -> Result<HttpResponse, Error> {
+async fn delete_dish_by_name(name: String, state: CurrentTenant) -> Result<HttpResponse, Error> {
     let res = state.delete_dish_by_name(name);
     
     match res {
@@ -173,4 +384,57 @@ async fn delete_dish_by_name(name: String, state: Data<AppState>) -> Result<Http
             )
         }
     }
-}
\ No newline at end of file
+}
+
+#[utoipa::path(
+    tag = "dishes",
+    params(("ID" = i32, Path, description = "ID of the dish")),
+    request_body = CreateDishRequest,
+    responses(
+        (status = 200, description = "ID of the updated dish", body = i32, headers(("ETag" = String, description = "New version of the dish"))),
+        (status = 404, description = "Dish not found (-5)", body = i32),
+        (status = 412, description = "The dish changed since the version given in If-Match (-9)", body = i32),
+        (status = 413, description = "Request body is too large (-8)", body = i32),
+        (status = 415, description = "Content-Type is not JSON, YAML or MessagePack (0)", body = i32),
+        (status = 422, description = "Missing name (-1), another dish already has the name (-2), name not recognized (-3), nutrition API unreachable (-4) or multi-item match not accepted (-7)", body = i32)
+    )
+)]
+#[put("/dishes/{ID}")]
+pub async fn update_dish(_auth: Authorized<Editor>, req: HttpRequest, path: Path<i32>, data: Body<CreateDishRequest>, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    let data = data.into_inner();
+
+    let res = state.update_dish(path.into_inner(), data.name, &data.multi_item, &data.items, if_match(&req)).await;
+
+    match res {
+        Ok(id) => {
+            let version = state.get_dish_by_id(id).map(|dish| dish.version()).unwrap_or_default();
+
+            Ok(
+                tagged(HttpResponse::Ok(), version, Format::Json)
+                    .content_type(ContentType::json())
+                    .json(id)
+            )
+        },
+        Err(-5) => {
+            Ok(
+                HttpResponse::NotFound()
+                    .content_type(ContentType::json())
+                    .json(-5)
+            )
+        },
+        Err(-9) => {
+            Ok(
+                HttpResponse::PreconditionFailed()
+                    .content_type(ContentType::json())
+                    .json(-9)
+            )
+        },
+        Err(err_id) => {
+            Ok(
+                HttpResponse::UnprocessableEntity()
+                    .content_type(ContentType::json())
+                    .json(err_id)
+            )
+        }
+    }
+}
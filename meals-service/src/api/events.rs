@@ -0,0 +1,144 @@
+use std::time::Duration;
+
+use actix_web::{
+    get,
+    web::{Bytes, Data},
+    HttpRequest,
+    HttpResponse,
+    http::header::{CacheControl, CacheDirective}
+};
+
+use futures::stream::{self, StreamExt};
+
+use tokio::{
+    sync::broadcast::{error::RecvError, Receiver},
+    time::{timeout_at, Instant}
+};
+
+use crate::{
+    repository::{
+        state::AppState,
+        events::Event
+    },
+    api::auth::{Authorized, Reader, CurrentTenant}
+};
+
+// Comment sent when nothing else has been, so proxies don't close idle connections
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+#[utoipa::path(
+    tag = "events",
+    params(("Last-Event-ID" = Option<u64>, Header, description = "ID of the last event received, to resume after it")),
+    responses(
+        (status = 200, description = "Stream of dish.created, dish.updated, dish.deleted, meal.created, meal.updated and meal.deleted events of the tenant, starting with a reset event when the ones after Last-Event-ID are no longer kept", body = String, content_type = "text/event-stream")
+    )
+)]
+#[get("/events")]
+pub async fn get_events(_auth: Authorized<Reader>, req: HttpRequest, state: Data<AppState>, tenant: CurrentTenant) -> HttpResponse {
+    let last_id = req.headers()
+        .get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok());
+
+    let (missed, receiver) = state.events().subscribe(last_id);
+
+    let mut ended = state.events().streams_ended();
+
+    // Event IDs are shared by all tenants, so a tenant's stream may skip some
+    let tenant = tenant.name().to_string();
+
+    // Events too old to be buffered can't be replayed, so the client is told to reload the dishes
+    // and meals instead and carries on from the latest event
+    let missed: Vec<Bytes> = match missed.complete {
+        true => missed.events.iter().filter(|event| event.tenant == tenant).map(to_message).collect(),
+        false => vec![reset_message(missed.last_id)]
+    };
+
+    let missed = stream::iter(missed).map(Ok::<Bytes, actix_web::Error>);
+
+    let live = stream::unfold((receiver, tenant), |(mut receiver, tenant): (Receiver<Event>, String)| async move {
+        // Events of other tenants don't count as activity, so they don't delay the keep-alive
+        let deadline = Instant::now() + KEEP_ALIVE;
+
+        loop {
+            match timeout_at(deadline, receiver.recv()).await {
+                Ok(Ok(event)) if event.tenant != tenant => continue,
+                Ok(Ok(event)) => return Some((Ok(to_message(&event)), (receiver, tenant))),
+                Err(_) => return Some((Ok(Bytes::from_static(b": keep-alive\n\n")), (receiver, tenant))),
+                // A client too slow to keep up is disconnected and can resume from the buffer with
+                // Last-Event-ID, as can clients of a server shutting down once another one is up
+                Ok(Err(RecvError::Lagged(_))) | Ok(Err(RecvError::Closed)) => return None
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .streaming(missed.chain(live.take_until(async move {
+            let _ = ended.wait_for(|ended| *ended).await;
+        })))
+}
+
+fn to_message(event: &Event) -> Bytes {
+    Bytes::from(format!("id: {}\nevent: {}\ndata: {}\n\n", event.id, event.name(), event.data))
+}
+
+fn reset_message(last_id: u64) -> Bytes {
+    Bytes::from(format!("id: {}\nevent: reset\ndata: {{\"last_id\":{}}}\n\n", last_id, last_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        test,
+        web::Data,
+        App
+    };
+
+    use serde_json::json;
+
+    use crate::repository::{
+        state::AppState,
+        tenants::DEFAULT_TENANT,
+        events::{Action, Resource}
+    };
+
+    use super::get_events;
+
+    // Ends the streams first so that they stop after the buffered events
+    async fn resume(state: AppState, last_id: &str) -> String {
+        state.events().end_streams();
+
+        let app = test::init_service(App::new().app_data(Data::new(state)).service(get_events)).await;
+        let req = test::TestRequest::get().uri("/events").insert_header(("Last-Event-ID", last_id)).to_request();
+
+        String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap()
+    }
+
+    fn state_with(events: &[&str]) -> AppState {
+        let state = AppState::new();
+
+        state.create_tenant(DEFAULT_TENANT);
+
+        for (id, tenant) in events.iter().enumerate() {
+            state.events().publish(tenant, Resource::Dish, Action::Created, &json!({ "ID": id + 1 }));
+        }
+
+        state
+    }
+
+    #[actix_web::test]
+    async fn resumes_with_the_tenants_buffered_events() {
+        let body = resume(state_with(&[DEFAULT_TENANT, "other", DEFAULT_TENANT, DEFAULT_TENANT]), "1").await;
+
+        assert_eq!(body, "id: 3\nevent: dish.created\ndata: {\"ID\":3}\n\nid: 4\nevent: dish.created\ndata: {\"ID\":4}\n\n");
+    }
+
+    #[actix_web::test]
+    async fn sends_a_reset_when_events_cannot_be_replayed() {
+        let body = resume(state_with(&[DEFAULT_TENANT, DEFAULT_TENANT]), "7").await;
+
+        assert_eq!(body, "id: 2\nevent: reset\ndata: {\"last_id\":2}\n\n");
+    }
+}
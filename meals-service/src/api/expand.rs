@@ -0,0 +1,186 @@
+use actix_web::web::Query;
+
+use serde::{Serialize, Deserialize};
+
+use utoipa::{IntoParams, ToSchema};
+
+use crate::repository::{
+    state::{Tenant, Dish, Meal},
+    listing::{Field, Listable}
+};
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExpandParams {
+    /// Set to dishes to embed the appetizer, main and dessert instead of their IDs
+    expand: Option<String>
+}
+
+// Whether the query string asks for meals with their dishes embedded. Other values are rejected
+// with -1 so that typos don't silently return IDs
+pub fn parse_expand(query: &str) -> Result<bool, i32> {
+    match Query::<ExpandParams>::from_query(query).map(Query::into_inner) {
+        Ok(ExpandParams { expand: None }) => Ok(false),
+        Ok(ExpandParams { expand: Some(expand) }) if expand == "dishes" => Ok(true),
+        _ => Err(-1)
+    }
+}
+
+// Share of the meal's calories, sodium and sugar that comes from one course, in percent
+#[derive(Serialize, Debug, ToSchema)]
+pub struct Contribution {
+    cal: f32,
+    sodium: f32,
+    sugar: f32
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct Course {
+    #[serde(flatten)]
+    dish: Dish,
+    contribution: Contribution
+}
+
+// Meal with its courses replaced by the dishes themselves. A course is null once its dish has been
+// deleted
+#[derive(Serialize, ToSchema)]
+pub struct ExpandedMeal {
+    #[serde(rename = "ID")]
+    id: i32,
+    name: String,
+    appetizer: Option<Course>,
+    main: Option<Course>,
+    dessert: Option<Course>,
+    cal: f32,
+    sodium: f32,
+    sugar: f32
+}
+
+impl ExpandedMeal {
+    pub fn new(state: &Tenant, meal: &Meal) -> ExpandedMeal {
+        let [appetizer, main, dessert] = meal.courses().map(|course| {
+            let dish = state.get_dish_by_id(course?).ok()?;
+
+            Some(Course {
+                contribution: Contribution {
+                    cal: share(&dish, meal, Field::Cal),
+                    sodium: share(&dish, meal, Field::Sodium),
+                    sugar: share(&dish, meal, Field::Sugar)
+                },
+                dish
+            })
+        });
+
+        ExpandedMeal {
+            id: meal.id(),
+            name: meal.name().to_string(),
+            appetizer,
+            main,
+            dessert,
+            cal: meal.nutrient(Field::Cal).unwrap_or_default(),
+            sodium: meal.nutrient(Field::Sodium).unwrap_or_default(),
+            sugar: meal.nutrient(Field::Sugar).unwrap_or_default()
+        }
+    }
+}
+
+impl Listable for ExpandedMeal {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn nutrient(&self, field: Field) -> Option<f32> {
+        match field {
+            Field::Cal => Some(self.cal),
+            Field::Sodium => Some(self.sodium),
+            Field::Sugar => Some(self.sugar),
+            _ => None
+        }
+    }
+}
+
+// Rounded to one decimal place, and 0 when the meal has none of the nutrient at all
+fn share(dish: &Dish, meal: &Meal, field: Field) -> f32 {
+    match (dish.nutrient(field), meal.nutrient(field)) {
+        (Some(part), Some(total)) if total > 0.0 => (part / total * 1000.0).round() / 10.0,
+        _ => 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::repository::{
+        state::{AppState, Tenant},
+        listing::Listable
+    };
+
+    use super::{parse_expand, Contribution, ExpandedMeal};
+
+    // pasta (1), focaccia (2), chicken soup (3) and salad (4), with dinner made of the first three
+    fn tenant() -> Arc<Tenant> {
+        let (tenant, _) = AppState::new().create_tenant("default");
+
+        tenant.rebuild();
+        tenant.create_meal(String::from("dinner"), &1, &2, &3).unwrap();
+
+        tenant
+    }
+
+    fn expand(tenant: &Tenant) -> ExpandedMeal {
+        ExpandedMeal::new(tenant, &tenant.get_meal_by_name("dinner").unwrap())
+    }
+
+    fn shares(contribution: &Contribution) -> [f32; 3] {
+        [contribution.cal, contribution.sodium, contribution.sugar]
+    }
+
+    #[test]
+    fn only_dishes_can_be_expanded() {
+        assert_eq!(parse_expand(""), Ok(false));
+        assert_eq!(parse_expand("limit=1"), Ok(false));
+        assert_eq!(parse_expand("expand=dishes"), Ok(true));
+        assert_eq!(parse_expand("expand=dish"), Err(-1));
+        assert_eq!(parse_expand("expand="), Err(-1));
+    }
+
+    #[test]
+    fn embeds_each_course_with_its_share_of_the_meal() {
+        let meal = expand(&tenant());
+
+        let courses: Vec<(&str, [f32; 3])> = [&meal.appetizer, &meal.main, &meal.dessert]
+            .iter()
+            .map(|course| course.as_ref().unwrap())
+            .map(|course| (course.dish.name(), shares(&course.contribution)))
+            .collect();
+
+        assert_eq!(courses, [("pasta", [63.7, 1.5, 26.3]), ("focaccia", [32.0, 70.2, 47.4]), ("chicken soup", [4.2, 28.3, 26.3])]);
+        assert_eq!((meal.id, meal.name.as_str(), meal.cal, meal.sodium, meal.sugar), (1, "dinner", 500.0 + 251.4 + 33.2, 812.0, 1.0 + 1.8 + 1.0));
+    }
+
+    #[test]
+    fn courses_of_deleted_dishes_are_null() {
+        let tenant = tenant();
+
+        tenant.delete_dish_by_id(2).unwrap();
+
+        let meal = expand(&tenant);
+
+        assert!(meal.appetizer.is_some());
+        assert!(meal.main.is_none());
+        assert!(meal.dessert.is_some());
+    }
+
+    #[test]
+    fn serializes_courses_as_dishes_with_a_contribution() {
+        let json = serde_json::to_string(&expand(&tenant()).appetizer).unwrap();
+
+        assert!(json.starts_with(r#"{"ID":1,"name":"pasta","cal":500.0,"#), "{}", json);
+        assert!(json.ends_with(r#""contribution":{"cal":63.7,"sodium":1.5,"sugar":26.3}}"#), "{}", json);
+    }
+}
@@ -0,0 +1,349 @@
+use std::sync::Arc;
+
+use actix_web::{
+    get,
+    post,
+    web::Data,
+    HttpResponse,
+    http::header::{ContentType, CacheControl, CacheDirective}
+};
+
+use async_graphql::{
+    http::GraphiQLSource,
+    Context,
+    EmptySubscription,
+    ErrorExtensions,
+    InputObject,
+    Object,
+    Result,
+    Schema
+};
+
+use crate::{
+    repository::{
+        state::{Tenant, Dish, DishItem, Meal},
+        listing::{Field, Comparison, Direction, Filter, Listable, ListOptions},
+        nutrition_api_client::MultiItemPolicy,
+        api_keys::Role
+    },
+    api::{
+        body::Body,
+        v2::ApiError,
+        auth::{Authorized, Reader, CurrentTenant}
+    }
+};
+
+// Deepest nesting a query may use. Meals only point at dishes, so anything deeper is a mistake
+const MAX_DEPTH: usize = 8;
+
+pub type MealsSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+pub fn schema() -> MealsSchema {
+    Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .finish()
+}
+
+// Keeps the dishes or meals whose field compares to the value as given, e.g.
+// {field: CAL, comparison: LT, value: 500}
+#[derive(InputObject)]
+pub struct FilterInput {
+    /// CAL, SIZE, SODIUM or SUGAR. Filters on ID or NAME, and on SIZE for meals, match nothing
+    field: Field,
+    /// LT, LTE, GT or GTE
+    comparison: Comparison,
+    value: f32
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    // Dishes matching every filter, in the requested order
+    async fn dishes(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] filters: Vec<FilterInput>,
+        sort: Option<Field>,
+        #[graphql(default)] order: Direction,
+        #[graphql(default)] offset: usize,
+        limit: Option<usize>
+    ) -> Result<Vec<DishObject>> {
+        let options = list_options(filters, sort, order, offset, limit);
+
+        Ok(state(ctx)?.get_dishes(&options).1.into_iter().map(DishObject).collect())
+    }
+
+    // Dish with the given ID or name, or null when there is none
+    async fn dish(&self, ctx: &Context<'_>, id: Option<i32>, name: Option<String>) -> Result<Option<DishObject>> {
+        let state = state(ctx)?;
+
+        let dish = match (id, name) {
+            (Some(id), None) => state.get_dish_by_id(id),
+            (None, Some(name)) => state.get_dish_by_name(name),
+            _ => return Err(error(-1))
+        };
+
+        Ok(found(dish)?.map(DishObject))
+    }
+
+    // Meals matching every filter, in the requested order
+    async fn meals(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] filters: Vec<FilterInput>,
+        sort: Option<Field>,
+        #[graphql(default)] order: Direction,
+        #[graphql(default)] offset: usize,
+        limit: Option<usize>
+    ) -> Result<Vec<MealObject>> {
+        let options = list_options(filters, sort, order, offset, limit);
+
+        Ok(state(ctx)?.get_meals(&options).1.into_iter().map(MealObject).collect())
+    }
+
+    // Meal with the given ID or name, or null when there is none
+    async fn meal(&self, ctx: &Context<'_>, id: Option<i32>, name: Option<String>) -> Result<Option<MealObject>> {
+        let state = state(ctx)?;
+
+        let meal = match (id, name) {
+            (Some(id), None) => state.get_meal_by_id(id),
+            (None, Some(name)) => state.get_meal_by_name(&name),
+            _ => return Err(error(-1))
+        };
+
+        Ok(found(meal)?.map(MealObject))
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    async fn create_dish(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        #[graphql(default)] multi_item: MultiItemPolicy,
+        #[graphql(default)] items: Vec<String>
+    ) -> Result<DishObject> {
+        require(ctx, Role::Editor)?;
+
+        let state = state(ctx)?;
+
+        let id = state.create_dish(name, &multi_item, &items).await.map_err(error)?;
+
+        state.get_dish_by_id(id).map(DishObject).map_err(error)
+    }
+
+    async fn create_meal(&self, ctx: &Context<'_>, name: String, appetizer: i32, main: i32, dessert: i32) -> Result<MealObject> {
+        require(ctx, Role::Editor)?;
+
+        let state = state(ctx)?;
+
+        let id = state.create_meal(name, &appetizer, &main, &dessert).map_err(error)?;
+
+        state.get_meal_by_id(id).map(MealObject).map_err(error)
+    }
+
+    async fn update_meal(&self, ctx: &Context<'_>, id: i32, name: String, appetizer: i32, main: i32, dessert: i32) -> Result<MealObject> {
+        require(ctx, Role::Editor)?;
+
+        let state = state(ctx)?;
+
+        let id = state.update_meal(&id, &name, &appetizer, &main, &dessert, |_| true).map_err(error)?;
+
+        state.get_meal_by_id(id).map(MealObject).map_err(error)
+    }
+}
+
+// Dish, DishItem and Meal as GraphQL objects. Nutrients are converted through their shortest
+// decimal form, so 251.4 isn't widened to 251.39999389648438
+pub struct DishObject(Dish);
+
+#[Object(name = "Dish")]
+impl DishObject {
+    async fn id(&self) -> i32 {
+        self.0.id()
+    }
+
+    async fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    async fn cal(&self) -> f64 {
+        decimal(self.0.nutrient(Field::Cal))
+    }
+
+    async fn size(&self) -> f64 {
+        decimal(self.0.nutrient(Field::Size))
+    }
+
+    async fn sodium(&self) -> f64 {
+        decimal(self.0.nutrient(Field::Sodium))
+    }
+
+    async fn sugar(&self) -> f64 {
+        decimal(self.0.nutrient(Field::Sugar))
+    }
+
+    // Every item the nutrition API matched, a single one for most names
+    async fn items(&self) -> Vec<DishItemObject> {
+        self.0.items().iter().cloned().map(DishItemObject).collect()
+    }
+}
+
+pub struct DishItemObject(DishItem);
+
+#[Object(name = "DishItem")]
+impl DishItemObject {
+    async fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    async fn cal(&self) -> f64 {
+        decimal(self.0.nutrient(Field::Cal))
+    }
+
+    async fn size(&self) -> f64 {
+        decimal(self.0.nutrient(Field::Size))
+    }
+
+    async fn sodium(&self) -> f64 {
+        decimal(self.0.nutrient(Field::Sodium))
+    }
+
+    async fn sugar(&self) -> f64 {
+        decimal(self.0.nutrient(Field::Sugar))
+    }
+}
+
+pub struct MealObject(Meal);
+
+#[Object(name = "Meal")]
+impl MealObject {
+    async fn id(&self) -> i32 {
+        self.0.id()
+    }
+
+    async fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    // Courses resolve to the dishes they refer to, and are null once a dish has been deleted
+    async fn appetizer(&self, ctx: &Context<'_>) -> Result<Option<DishObject>> {
+        course(ctx, self.0.courses()[0])
+    }
+
+    async fn main(&self, ctx: &Context<'_>) -> Result<Option<DishObject>> {
+        course(ctx, self.0.courses()[1])
+    }
+
+    async fn dessert(&self, ctx: &Context<'_>) -> Result<Option<DishObject>> {
+        course(ctx, self.0.courses()[2])
+    }
+
+    async fn cal(&self) -> f64 {
+        decimal(self.0.nutrient(Field::Cal))
+    }
+
+    async fn sodium(&self) -> f64 {
+        decimal(self.0.nutrient(Field::Sodium))
+    }
+
+    async fn sugar(&self) -> f64 {
+        decimal(self.0.nutrient(Field::Sugar))
+    }
+}
+
+fn course(ctx: &Context<'_>, id: Option<i32>) -> Result<Option<DishObject>> {
+    match id {
+        Some(id) => Ok(found(state(ctx)?.get_dish_by_id(id))?.map(DishObject)),
+        None => Ok(None)
+    }
+}
+
+fn decimal(value: Option<f32>) -> f64 {
+    value.map(|value| value.to_string().parse().unwrap_or(value as f64)).unwrap_or_default()
+}
+
+// Dishes and meals of the tenant the request was sent for
+fn state<'a>(ctx: &Context<'a>) -> Result<&'a Arc<Tenant>> {
+    ctx.data::<Arc<Tenant>>()
+}
+
+// Queries only need the reader role checked by post_graphql, while mutations need more
+fn require(ctx: &Context<'_>, minimum: Role) -> Result<()> {
+    match ctx.data::<Role>() {
+        Ok(role) if *role >= minimum => Ok(()),
+        _ => Err(error(-13))
+    }
+}
+
+fn list_options(filters: Vec<FilterInput>, sort: Option<Field>, order: Direction, offset: usize, limit: Option<usize>) -> ListOptions {
+    ListOptions {
+        filters: filters.into_iter().map(|filter| Filter::new(filter.field, filter.comparison, filter.value)).collect(),
+        sort,
+        direction: order,
+        offset,
+        limit
+    }
+}
+
+// Lookups that find nothing give null rather than an error, as is usual in GraphQL
+fn found<T>(result: Result<T, i32>) -> Result<Option<T>> {
+    match result {
+        Ok(item) => Ok(Some(item)),
+        Err(-5) => Ok(None),
+        Err(err_id) => Err(error(err_id))
+    }
+}
+
+// Same message as the /v2 routes, with the code under extensions.code
+fn error(err_id: i32) -> async_graphql::Error {
+    let err = ApiError::new(err_id);
+
+    async_graphql::Error::new(err.message()).extend_with(|_, extensions| extensions.set("code", err.code()))
+}
+
+#[utoipa::path(
+    tag = "graphql",
+    request_body(content = Object, description = "GraphQL request with query, and optionally variables and operationName"),
+    responses(
+        (status = 200, description = "GraphQL response with data and any errors, each carrying the error code under extensions.code", body = Object),
+        (status = 413, description = "Request body is too large (-8)", body = i32),
+        (status = 415, description = "Content-Type is not JSON, YAML or MessagePack (0)", body = i32),
+        (status = 422, description = "Request body is not a GraphQL request (-1)", body = i32)
+    )
+)]
+#[post("/graphql")]
+pub async fn post_graphql(auth: Authorized<Reader>, data: Body<async_graphql::Request>, schema: Data<MealsSchema>, state: CurrentTenant) -> HttpResponse {
+    let response = schema.execute(data.into_inner().data(state.into_inner()).data(auth.role())).await;
+
+    let mut res = HttpResponse::Ok();
+
+    // Mutations that failed on the nutrition API (-4) run again when retried under the same
+    // Idempotency-Key, rather than having the error replayed
+    let unreachable = async_graphql::Value::from(-4);
+
+    if response.errors.iter().any(|err| err.extensions.as_ref().and_then(|extensions| extensions.get("code")) == Some(&unreachable)) {
+        res.insert_header(CacheControl(vec![CacheDirective::NoStore]));
+    }
+
+    res
+        .content_type(ContentType::json())
+        .json(response)
+}
+
+#[utoipa::path(
+    tag = "graphql",
+    responses(
+        (status = 200, description = "GraphiQL page for exploring the schema and running queries", body = String, content_type = "text/html")
+    )
+)]
+#[get("/graphql")]
+pub async fn get_graphiql() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(GraphiQLSource::build().endpoint("/graphql").title("Meals Service").finish())
+}
@@ -0,0 +1,83 @@
+use actix_web::{
+    get,
+    web::Data,
+    HttpResponse,
+    http::header::ContentType
+};
+
+use serde_json::json;
+
+use crate::repository::{
+    state::AppState,
+    health::{Readiness, Status, DependencyHealth, StorageHealth}
+};
+
+#[utoipa::path(
+    tag = "health",
+    responses(
+        (status = 200, description = "The process is running and answering requests", body = Object, example = json!({"status": "up"}))
+    )
+)]
+#[get("/health/live")]
+pub async fn get_liveness() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::json())
+        .json(json!({"status": "up"}))
+}
+
+#[utoipa::path(
+    tag = "health",
+    responses(
+        (status = 200, description = "The service is ready for traffic", body = Object, example = json!({"status": "ready"})),
+        (status = 503, description = "The service is shutting down", body = Object, example = json!({"status": "draining"}))
+    )
+)]
+#[get("/health/ready")]
+pub async fn get_readiness(state: Data<AppState>) -> HttpResponse {
+    let readiness = state.get_readiness();
+
+    let mut res = match readiness {
+        Readiness::Ready => HttpResponse::Ok(),
+        _ => HttpResponse::ServiceUnavailable()
+    };
+
+    res
+        .content_type(ContentType::json())
+        .json(json!({"status": readiness}))
+}
+
+#[utoipa::path(
+    tag = "health",
+    responses(
+        (status = 200, description = "The nutrition API is up", body = DependencyHealth),
+        (status = 503, description = "The nutrition API is down", body = DependencyHealth)
+    )
+)]
+#[get("/health/dependencies")]
+pub async fn get_dependencies(state: Data<AppState>) -> HttpResponse {
+    let nutrition_api = state.nutrition_api_probe().check(state.nutrition_api_breaker()).await;
+
+    let tenants = state.get_tenants();
+
+    let storage = StorageHealth {
+        kind: String::from("memory"),
+        dishes: tenants.iter().map(|tenant| tenant.dishes).sum(),
+        meals: tenants.iter().map(|tenant| tenant.meals).sum()
+    };
+
+    let status = nutrition_api.status;
+
+    let mut res = match status {
+        Status::Up => HttpResponse::Ok(),
+        Status::Down => HttpResponse::ServiceUnavailable()
+    };
+
+    res
+        .content_type(ContentType::json())
+        .json(DependencyHealth {
+            status,
+            readiness: state.get_readiness(),
+            nutrition_api,
+            storage
+        })
+}
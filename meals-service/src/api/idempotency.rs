@@ -0,0 +1,419 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::Mutex,
+    time::{Duration, Instant}
+};
+
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{StatusCode, header::{self, HeaderMap, HeaderName}},
+    middleware::Next,
+    web::{Bytes, Data},
+    Error,
+    HttpMessage,
+    HttpResponse
+};
+
+use crate::{
+    repository::{state::AppState, api_keys::Grant},
+    api::{body::read_bytes, v2::error_for, auth::{TENANT, is_enabled}}
+};
+
+const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+// Responses are kept for a day unless IDEMPOTENCY_WINDOW gives another number of seconds
+const DEFAULT_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Most keys kept at once unless IDEMPOTENCY_MAX_KEYS gives another number. The oldest ones make
+// room for new ones before their window is over
+const DEFAULT_CAPACITY: usize = 100_000;
+
+const MAX_KEY_LENGTH: usize = 255;
+
+#[derive(Clone)]
+struct StoredResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes
+}
+
+struct Entry {
+    fingerprint: u64,
+    created: Instant,
+    // None until the first request with the key has finished
+    response: Option<StoredResponse>
+}
+
+// Keys with the time they were added, oldest first, so expired ones are found without scanning
+// them all. A key freed early stays queued until its turn comes, and is then skipped if it has
+// been added again since
+#[derive(Default)]
+struct Entries {
+    by_key: HashMap<String, Entry>,
+    expiry: VecDeque<(Instant, String)>
+}
+
+impl Entries {
+    fn pop_oldest(&mut self) {
+        if let Some((created, key)) = self.expiry.pop_front() {
+            if self.by_key.get(&key).is_some_and(|entry| entry.created == created) {
+                self.by_key.remove(&key);
+            }
+        }
+    }
+}
+
+enum Lookup {
+    New,
+    Replay(StoredResponse),
+    InProgress,
+    Mismatch
+}
+
+// Responses to POST requests carrying an Idempotency-Key, so that retries get the original
+// response instead of creating the dish or meal again
+pub struct IdempotencyStore {
+    window: Duration,
+    capacity: usize,
+    entries: Mutex<Entries>
+}
+
+impl IdempotencyStore {
+    pub fn new(window: Duration, capacity: usize) -> IdempotencyStore {
+        IdempotencyStore {
+            window,
+            capacity: capacity.max(1),
+            entries: Mutex::new(Entries::default())
+        }
+    }
+
+    pub fn from_env() -> IdempotencyStore {
+        let window = std::env::var("IDEMPOTENCY_WINDOW")
+            .ok()
+            .and_then(|seconds| seconds.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_WINDOW);
+
+        let capacity = std::env::var("IDEMPOTENCY_MAX_KEYS")
+            .ok()
+            .and_then(|keys| keys.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_CAPACITY);
+
+        IdempotencyStore::new(window, capacity)
+    }
+
+    fn begin(&self, key: &str, fingerprint: u64) -> Lookup {
+        let mut entries = self.entries.lock().unwrap();
+
+        while entries.expiry.front().is_some_and(|(created, _)| created.elapsed() >= self.window) {
+            entries.pop_oldest();
+        }
+
+        match entries.by_key.get(key) {
+            Some(entry) if entry.fingerprint != fingerprint => Lookup::Mismatch,
+            Some(Entry { response: Some(response), .. }) => Lookup::Replay(response.clone()),
+            Some(_) => Lookup::InProgress,
+            None => {
+                while entries.expiry.len() >= self.capacity {
+                    entries.pop_oldest();
+                }
+
+                let created = Instant::now();
+
+                entries.by_key.insert(key.to_string(), Entry { fingerprint, created, response: None });
+                entries.expiry.push_back((created, key.to_string()));
+
+                Lookup::New
+            }
+        }
+    }
+
+    fn finish(&self, key: &str, response: StoredResponse) {
+        if let Some(entry) = self.entries.lock().unwrap().by_key.get_mut(key) {
+            entry.response = Some(response);
+        }
+    }
+
+    fn abandon(&self, key: &str) {
+        self.entries.lock().unwrap().by_key.remove(key);
+    }
+}
+
+// Frees the key if the request never produces a response worth keeping, including when the
+// client disconnects and the handler is dropped, so a retry can run it again
+struct Pending<'a> {
+    store: &'a IdempotencyStore,
+    key: &'a str,
+    done: bool
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.store.abandon(self.key);
+        }
+    }
+}
+
+pub async fn idempotency(mut req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, Error> {
+    let store = req.app_data::<Data<IdempotencyStore>>().cloned();
+
+    let key = req.headers().get(&IDEMPOTENCY_KEY).map(|value| value.to_str().map(String::from));
+
+    let (store, key) = match (store, key) {
+        (Some(store), Some(key)) if req.method() == actix_web::http::Method::POST => (store, key),
+        _ => return next.call(req).await.map(ServiceResponse::map_into_boxed_body)
+    };
+
+    let key = match key {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LENGTH => key,
+        _ => return Ok(reject(req, -1))
+    };
+
+    // Callers that haven't authenticated are left to the route, which refuses them if it needs a
+    // credential, rather than being answered from the store
+    let principal = match principal(&req) {
+        Some(principal) => principal,
+        None => return next.call(req).await.map(ServiceResponse::map_into_boxed_body)
+    };
+
+    // Each caller, tenant and route has its own keys, so a response is only ever replayed to the
+    // caller it was made for
+    let key = format!("{}\n{}\n{} {}\n{}", tenant(&req), principal, req.method(), req.path(), key);
+
+    let (http_req, payload) = req.parts_mut();
+
+    let body = match read_bytes(http_req, payload).await {
+        Ok(body) => body,
+        Err(err) => return Ok(reject(req, err.code()))
+    };
+
+    req.set_payload(body.clone().into());
+
+    let mut hasher = DefaultHasher::new();
+
+    req.query_string().hash(&mut hasher);
+    body.hash(&mut hasher);
+
+    match store.begin(&key, hasher.finish()) {
+        Lookup::Mismatch => return Ok(reject(req, -10)),
+        Lookup::InProgress => return Ok(reject(req, -11)),
+        Lookup::Replay(stored) => return Ok(req.into_response(replay(stored))),
+        Lookup::New => {}
+    }
+
+    let mut pending = Pending { store: &store, key: &key, done: false };
+
+    let res = next.call(req).await?;
+
+    // Server errors may not have changed anything, so retries are allowed to try again. So are
+    // requests that weren't allowed, which can be retried with another API key. Responses carrying
+    // secrets, such as new API keys, are marked no-store and never kept
+    if res.status().is_server_error() || res.status() == StatusCode::UNAUTHORIZED || res.status() == StatusCode::FORBIDDEN || is_no_store(res.headers()) {
+        return Ok(res.map_into_boxed_body())
+    }
+
+    let (req, res) = res.into_parts();
+    let (res, res_body) = res.into_parts();
+
+    let bytes = match body::to_bytes(res_body).await {
+        Ok(bytes) => bytes,
+        Err(_) => return Ok(ServiceResponse::new(req, HttpResponse::InternalServerError().finish()))
+    };
+
+    let mut headers = res.headers().clone();
+
+    headers.remove(header::DATE);
+    headers.remove(header::CONTENT_LENGTH);
+
+    store.finish(&key, StoredResponse { status: res.status(), headers, body: bytes.clone() });
+
+    pending.done = true;
+
+    Ok(ServiceResponse::new(req, res.set_body(BoxBody::new(bytes))))
+}
+
+// The credential's principal and role, "anonymous" while authentication is disabled, or None for
+// requests without a credential
+fn principal(req: &ServiceRequest) -> Option<String> {
+    let enabled = req.app_data::<Data<AppState>>().is_some_and(|state| is_enabled(state));
+
+    match req.extensions().get::<Grant>() {
+        Some(grant) => Some(format!("{} {}", grant.principal, grant.role)),
+        None if !enabled => Some(String::from("anonymous")),
+        None => None
+    }
+}
+
+fn is_no_store(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CACHE_CONTROL)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|directive| directive.trim().eq_ignore_ascii_case("no-store")))
+}
+
+fn tenant(req: &ServiceRequest) -> String {
+    let header = req.headers().get(TENANT).and_then(|value| value.to_str().ok());
+
+    match header {
+        Some(name) => name.to_string(),
+        None => req.extensions().get::<Grant>().and_then(|grant| grant.tenant.clone()).unwrap_or_default()
+    }
+}
+
+fn replay(stored: StoredResponse) -> HttpResponse {
+    let mut builder = HttpResponse::build(stored.status);
+
+    for (name, value) in stored.headers.iter() {
+        builder.append_header((name.clone(), value.clone()));
+    }
+
+    builder
+        .insert_header((IDEMPOTENT_REPLAYED, "true"))
+        .body(stored.body)
+}
+
+fn reject(req: ServiceRequest, code: i32) -> ServiceResponse<BoxBody> {
+    let res = error_for(req.path(), code);
+
+    req.into_response(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicI32, Ordering},
+        time::Duration
+    };
+
+    use actix_web::{
+        http::{StatusCode, header::{HeaderMap, CacheControl, CacheDirective}},
+        web::{self, Bytes, Data},
+        middleware::from_fn,
+        test::{init_service, call_service, read_body, TestRequest},
+        App,
+        HttpResponse
+    };
+
+    use super::{IdempotencyStore, Lookup, StoredResponse, idempotency};
+
+    fn response() -> StoredResponse {
+        StoredResponse { status: StatusCode::CREATED, headers: HeaderMap::new(), body: Bytes::from_static(b"1") }
+    }
+
+    #[test]
+    fn finished_requests_are_replayed() {
+        let store = IdempotencyStore::new(Duration::from_secs(60), 10);
+
+        assert!(matches!(store.begin("a", 1), Lookup::New));
+        assert!(matches!(store.begin("a", 1), Lookup::InProgress));
+
+        store.finish("a", response());
+
+        assert!(matches!(store.begin("a", 1), Lookup::Replay(stored) if stored.body == "1"));
+        assert!(matches!(store.begin("a", 2), Lookup::Mismatch));
+    }
+
+    #[test]
+    fn abandoned_keys_can_be_used_again() {
+        let store = IdempotencyStore::new(Duration::from_secs(60), 10);
+
+        store.begin("a", 1);
+        store.abandon("a");
+
+        assert!(matches!(store.begin("a", 2), Lookup::New));
+    }
+
+    #[test]
+    fn keys_expire_after_the_window() {
+        let store = IdempotencyStore::new(Duration::ZERO, 10);
+
+        store.begin("a", 1);
+        store.finish("a", response());
+
+        assert!(matches!(store.begin("a", 1), Lookup::New));
+        assert_eq!(store.entries.lock().unwrap().by_key.len(), 1);
+    }
+
+    #[test]
+    fn the_oldest_keys_make_room_for_new_ones() {
+        let store = IdempotencyStore::new(Duration::from_secs(60), 2);
+
+        for key in ["a", "b", "c"] {
+            store.begin(key, 1);
+            store.finish(key, response());
+        }
+
+        let entries = store.entries.lock().unwrap();
+
+        assert_eq!(entries.by_key.len(), 2);
+        assert!(!entries.by_key.contains_key("a"));
+        assert_eq!(entries.expiry.len(), 2);
+    }
+
+    #[test]
+    fn keys_added_again_are_not_expired_by_their_earlier_use() {
+        let store = IdempotencyStore::new(Duration::from_secs(60), 2);
+
+        store.begin("a", 1);
+        store.abandon("a");
+        store.begin("a", 1);
+        store.finish("a", response());
+
+        // Drops the queued earlier use of "a", which must leave the current one in place
+        store.begin("b", 1);
+
+        assert!(matches!(store.begin("a", 1), Lookup::Replay(_)));
+    }
+
+    // Fails with the error code it is given, marking nutrition API failures no-store as the dish
+    // handlers do
+    async fn failing(code: Data<i32>, calls: Data<AtomicI32>) -> HttpResponse {
+        calls.fetch_add(1, Ordering::SeqCst);
+
+        let code = **code;
+        let mut res = HttpResponse::UnprocessableEntity();
+
+        if code == -4 {
+            res.insert_header(CacheControl(vec![CacheDirective::NoStore]));
+        }
+
+        res.json(code)
+    }
+
+    async fn calls_for(code: i32) -> i32 {
+        let calls = Data::new(AtomicI32::new(0));
+
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(idempotency))
+                .app_data(Data::new(IdempotencyStore::new(Duration::from_secs(60), 10)))
+                .app_data(Data::new(code))
+                .app_data(calls.clone())
+                .route("/dishes", web::post().to(failing))
+        ).await;
+
+        for _ in 0..2 {
+            let req = TestRequest::post().uri("/dishes").insert_header(("Idempotency-Key", "k")).set_payload("{}").to_request();
+            let res = call_service(&app, req).await;
+
+            assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(read_body(res).await, code.to_string());
+        }
+
+        calls.load(Ordering::SeqCst)
+    }
+
+    #[actix_web::test]
+    async fn nutrition_api_failures_are_retried() {
+        assert_eq!(calls_for(-4).await, 2);
+    }
+
+    #[actix_web::test]
+    async fn other_client_errors_are_replayed() {
+        assert_eq!(calls_for(-3).await, 1);
+    }
+}
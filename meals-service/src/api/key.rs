@@ -0,0 +1,116 @@
+use actix_web::{
+    get,
+    post,
+    delete,
+    web::{Path, Data},
+    HttpResponse,
+    http::header::{ContentType, CacheControl, CacheDirective},
+};
+
+use serde::Deserialize;
+
+use utoipa::ToSchema;
+
+use crate::{
+    repository::{
+        state::AppState,
+        api_keys::{ApiKey, Role}
+    },
+    api::{
+        representation::{Format, reply},
+        body::Body,
+        auth::{Authorized, Admin}
+    }
+};
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct CreateKeyRequest {
+    pub role: Role,
+    pub name: Option<String>,
+    pub tenant: Option<String>
+}
+
+#[utoipa::path(
+    tag = "keys",
+    request_body = CreateKeyRequest,
+    responses(
+        (status = 201, description = "The new API key, including the key itself, which is not shown again", body = ApiKey),
+        (status = 401, description = "A valid API key or token is required (-12)", body = i32),
+        (status = 403, description = "The API key or token does not have the admin role (-13)", body = i32),
+        (status = 409, description = "Authentication is disabled (-16)", body = i32),
+        (status = 413, description = "Request body is too large (-8)", body = i32),
+        (status = 415, description = "Content-Type is not JSON, YAML or MessagePack (0)", body = i32),
+        (status = 422, description = "Unknown role or empty name (-1), or invalid tenant name (-15)", body = i32)
+    )
+)]
+#[post("/keys")]
+pub async fn create_key(_auth: Authorized<Admin>, data: Body<CreateKeyRequest>, state: Data<AppState>) -> HttpResponse {
+    let data = data.into_inner();
+
+    // Keys would be of no use, and anybody could make themselves one for when authentication is
+    // turned on
+    if !state.is_auth_required() {
+        return HttpResponse::Conflict()
+            .content_type(ContentType::json())
+            .json(-16)
+    }
+
+    match state.api_keys().create(data.role, data.name, data.tenant) {
+        Ok(key) => {
+            HttpResponse::Created()
+                .content_type(ContentType::json())
+                .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+                .json(key)
+        },
+        Err(err_id) => {
+            HttpResponse::UnprocessableEntity()
+                .content_type(ContentType::json())
+                .json(err_id)
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "keys",
+    responses(
+        (status = 200, description = "Every API key, without the keys themselves", body = Vec<ApiKey>),
+        (status = 401, description = "A valid API key or token is required (-12)", body = i32),
+        (status = 403, description = "The API key or token does not have the admin role (-13)", body = i32)
+    )
+)]
+#[get("/keys")]
+pub async fn get_keys(_auth: Authorized<Admin>, format: Format, state: Data<AppState>) -> HttpResponse {
+    reply(HttpResponse::Ok(), format, &state.api_keys().get_keys())
+}
+
+#[utoipa::path(
+    tag = "keys",
+    params(("ID" = i32, Path, description = "ID of the API key")),
+    responses(
+        (status = 200, description = "ID of the revoked API key", body = i32),
+        (status = 401, description = "A valid API key or token is required (-12)", body = i32),
+        (status = 403, description = "The API key or token does not have the admin role (-13)", body = i32),
+        (status = 404, description = "API key not found (-5)", body = i32),
+        (status = 409, description = "The API key is the last admin key (-17)", body = i32)
+    )
+)]
+#[delete("/keys/{ID}")]
+pub async fn delete_key(_auth: Authorized<Admin>, path: Path<i32>, state: Data<AppState>) -> HttpResponse {
+    match state.api_keys().revoke(path.into_inner()) {
+        Ok(id) => {
+            HttpResponse::Ok()
+                .content_type(ContentType::json())
+                .json(id)
+        },
+        Err(-17) => {
+            HttpResponse::Conflict()
+                .content_type(ContentType::json())
+                .json(-17)
+        },
+        Err(err_id) => {
+            HttpResponse::NotFound()
+                .content_type(ContentType::json())
+                .json(err_id)
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::str::FromStr;
+
+use actix_web::{
+    HttpResponse,
+    HttpResponseBuilder,
+    web::Query
+};
+
+use serde::{Serialize, Serializer, Deserialize};
+
+use utoipa::IntoParams;
+
+use crate::{
+    repository::listing::{Field, Comparison, Direction, Filter, Listable, ListOptions},
+    api::representation::{Format, reply}
+};
+
+// Query string of GET /dishes and GET /meals, e.g. ?cal_lt=500&sodium_gte=10&sort=cal&order=desc&offset=20&limit=10
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListParams {
+    /// Only include items with fewer calories
+    cal_lt: Option<f32>,
+    /// Only include items with at most this many calories
+    cal_lte: Option<f32>,
+    /// Only include items with more calories
+    cal_gt: Option<f32>,
+    /// Only include items with at least this many calories
+    cal_gte: Option<f32>,
+    /// Only include items with a smaller serving size in grams, never matches meals
+    size_lt: Option<f32>,
+    /// Only include items with at most this serving size in grams, never matches meals
+    size_lte: Option<f32>,
+    /// Only include items with a larger serving size in grams, never matches meals
+    size_gt: Option<f32>,
+    /// Only include items with at least this serving size in grams, never matches meals
+    size_gte: Option<f32>,
+    /// Only include items with less sodium in mg
+    sodium_lt: Option<f32>,
+    /// Only include items with at most this much sodium in mg
+    sodium_lte: Option<f32>,
+    /// Only include items with more sodium in mg
+    sodium_gt: Option<f32>,
+    /// Only include items with at least this much sodium in mg
+    sodium_gte: Option<f32>,
+    /// Only include items with less sugar in grams
+    sugar_lt: Option<f32>,
+    /// Only include items with at most this much sugar in grams
+    sugar_lte: Option<f32>,
+    /// Only include items with more sugar in grams
+    sugar_gt: Option<f32>,
+    /// Only include items with at least this much sugar in grams
+    sugar_gte: Option<f32>,
+    /// Field to sort by: id (default), name, cal, size, sodium or sugar
+    sort: Option<String>,
+    /// asc (default) or desc
+    order: Option<String>,
+    /// Number of matching items to skip
+    offset: Option<usize>,
+    /// Maximum number of items to return
+    limit: Option<usize>
+}
+
+impl ListParams {
+    pub fn into_options(self) -> Result<ListOptions, i32> {
+        let mut options = ListOptions::default();
+
+        let bounds = [
+            (Field::Cal, Comparison::Lt, self.cal_lt),
+            (Field::Cal, Comparison::Lte, self.cal_lte),
+            (Field::Cal, Comparison::Gt, self.cal_gt),
+            (Field::Cal, Comparison::Gte, self.cal_gte),
+            (Field::Size, Comparison::Lt, self.size_lt),
+            (Field::Size, Comparison::Lte, self.size_lte),
+            (Field::Size, Comparison::Gt, self.size_gt),
+            (Field::Size, Comparison::Gte, self.size_gte),
+            (Field::Sodium, Comparison::Lt, self.sodium_lt),
+            (Field::Sodium, Comparison::Lte, self.sodium_lte),
+            (Field::Sodium, Comparison::Gt, self.sodium_gt),
+            (Field::Sodium, Comparison::Gte, self.sodium_gte),
+            (Field::Sugar, Comparison::Lt, self.sugar_lt),
+            (Field::Sugar, Comparison::Lte, self.sugar_lte),
+            (Field::Sugar, Comparison::Gt, self.sugar_gt),
+            (Field::Sugar, Comparison::Gte, self.sugar_gte)
+        ];
+
+        for (field, comparison, value) in bounds {
+            if let Some(value) = value {
+                options.filters.push(Filter::new(field, comparison, value));
+            }
+        }
+
+        if let Some(sort) = self.sort {
+            options.sort = Some(Field::from_str(&sort).map_err(|_| -1)?);
+        }
+
+        if let Some(order) = self.order {
+            options.direction = Direction::from_str(&order).map_err(|_| -1)?;
+        }
+
+        options.offset = self.offset.unwrap_or(0);
+        options.limit = self.limit;
+
+        Ok(options)
+    }
+}
+
+pub fn parse_list_query(query: &str) -> Result<ListOptions, i32> {
+    match Query::<ListParams>::from_query(query) {
+        Ok(params) => params.into_inner().into_options(),
+        Err(_) => Err(-1)
+    }
+}
+
+// Serializes items as a JSON object keyed by ID like the original HashMap responses,
+// but keeps the order the items were sorted in
+pub struct KeyedList<'a, T: Listable + Serialize>(pub &'a Vec<T>);
+
+impl<'a, T: Listable + Serialize> Serialize for KeyedList<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|item| (item.id(), item)))
+    }
+}
+
+pub fn list_response<T: Listable + Serialize>(mut builder: HttpResponseBuilder, format: Format, total: usize, items: &Vec<T>) -> HttpResponse {
+    builder.insert_header(("X-Total-Count", total.to_string()));
+
+    reply(builder, format, &KeyedList(items))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::listing::{Field, Direction};
+
+    use super::parse_list_query;
+
+    #[test]
+    fn reads_filters_sort_and_paging() {
+        let options = parse_list_query("cal_lt=500&sodium_gte=10&sort=cal&order=desc&offset=20&limit=10").unwrap();
+
+        assert_eq!(options.filters.len(), 2);
+        assert_eq!(options.sort, Some(Field::Cal));
+        assert_eq!(options.direction, Direction::Desc);
+        assert_eq!(options.offset, 20);
+        assert_eq!(options.limit, Some(10));
+    }
+
+    #[test]
+    fn defaults_to_everything_in_id_order() {
+        let options = parse_list_query("").unwrap();
+
+        assert!(options.filters.is_empty());
+        assert_eq!(options.sort, None);
+        assert_eq!(options.direction, Direction::Asc);
+        assert_eq!(options.offset, 0);
+        assert_eq!(options.limit, None);
+    }
+
+    #[test]
+    fn accepts_id_in_either_case() {
+        assert_eq!(parse_list_query("sort=ID").unwrap().sort, Some(Field::Id));
+        assert_eq!(parse_list_query("sort=id").unwrap().sort, Some(Field::Id));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        for query in ["cal_lt=many", "sort=price", "order=up", "limit=-1", "offset=x"] {
+            assert_eq!(parse_list_query(query).err(), Some(-1), "{}", query);
+        }
+    }
+}
@@ -3,55 +3,52 @@ use actix_web::{
     post,
     delete,
     put,
-    web::{Path, Header, Data, Payload, BytesMut},
+    web::{Path, Query},
+    HttpRequest,
     HttpResponse,
     Error,
     http::header::ContentType,
 };
 
-use futures::StreamExt;
-
 use serde::Deserialize;
 
-use crate::repository::state::{AppState, Meal};
+use utoipa::ToSchema;
+
+use crate::{
+    repository::state::{Tenant, Meal},
+    api::{
+        path::decode_name,
+        search::{SearchQuery, MealSearchResult},
+        list::{ListParams, parse_list_query, list_response},
+        representation::{Format, reply},
+        body::Body,
+        conditional::{tagged, not_modified, if_match},
+        expand::{ExpandParams, ExpandedMeal, parse_expand},
+        auth::{Authorized, Reader, Editor, CurrentTenant}
+    }
+};
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, ToSchema)]
 pub struct CreateMealRequest {
-    name: String,
-    appetizer: i32,
-    main: i32,
-    dessert: i32
+    pub name: String,
+    pub appetizer: i32,
+    pub main: i32,
+    pub dessert: i32
 }
 
+#[utoipa::path(
+    tag = "meals",
+    request_body = CreateMealRequest,
+    responses(
+        (status = 201, description = "ID of the new meal", body = i32),
+        (status = 413, description = "Request body is too large (-8)", body = i32),
+        (status = 415, description = "Content-Type is not JSON, YAML or MessagePack (0)", body = i32),
+        (status = 422, description = "Missing parameter (-1), meal already exists (-2), unknown dish ID (-6) or meal quota of the tenant reached (-14)", body = i32)
+    )
+)]
 #[post("/meals")]
-pub async fn create_meal(content_type: Header<ContentType>, mut payload: Payload, state: Data<AppState>) -> Result<HttpResponse, Error> {
-    if content_type.to_string() != String::from("application/json") {
-        return Ok(
-            HttpResponse::UnsupportedMediaType()
-                .content_type(ContentType::json())
-                .json(0)
-        )
-    }
-
-    let data: CreateMealRequest;
-    let mut body = BytesMut::new();
-
-    while let Some(chunk) = payload.next().await {
-        let chunk = chunk?;
-
-        body.extend_from_slice(&chunk);
-    }
-
-    match serde_json::from_slice::<CreateMealRequest>(&body) {
-        Ok(body) => data = body,
-        _ => {
-            return Ok(
-                HttpResponse::UnprocessableEntity()
-                    .content_type(ContentType::json())
-                    .json(-1)
-            )
-        }
-    }
+pub async fn create_meal(_auth: Authorized<Editor>, data: Body<CreateMealRequest>, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    let data = data.into_inner();
 
     let meal_id = state.create_meal(data.name, &data.appetizer, &data.main, &data.dessert);
 
@@ -73,17 +70,79 @@ pub async fn create_meal(content_type: Header<ContentType>, mut payload: Payload
     }
 }
 
+#[utoipa::path(
+    tag = "meals",
+    params(ListParams, ExpandParams),
+    responses(
+        (status = 200, description = "Meals indexed by ID, as ExpandedMeal with expand=dishes", body = HashMap<String, Meal>,
+            headers(("X-Total-Count" = usize, description = "Number of meals matching the filters"), ("ETag" = String, description = "Version of the meal collection, and of the dish collection with expand=dishes"))),
+        (status = 304, description = "The meals haven't changed since the version given in If-None-Match"),
+        (status = 422, description = "Invalid query parameter (-1)", body = i32)
+    )
+)]
 #[get("/meals")]
-pub async fn get_meals(state: Data<AppState>) -> Result<HttpResponse, Error> {
-    let meals = state.get_meals();
+pub async fn get_meals(_auth: Authorized<Reader>, req: HttpRequest, format: Format, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    match parse_list_query(req.query_string()).and_then(|options| Ok((options, parse_expand(req.query_string())?))) {
+        Ok((options, false)) => {
+            let version = state.get_meals_version();
+
+            if let Some(res) = not_modified(&req, version, format) {
+                return Ok(res)
+            }
+
+            let (total, meals) = state.get_meals(&options);
+
+            Ok(list_response(tagged(HttpResponse::Ok(), version, format), format, total, &meals))
+        },
+        Ok((options, true)) => {
+            let version = (state.get_meals_version(), state.get_dishes_version());
 
-    Ok(
-        HttpResponse::Ok()
-            .content_type(ContentType::json())
-            .json(meals)
+            if let Some(res) = not_modified(&req, version, format) {
+                return Ok(res)
+            }
+
+            let (total, meals) = state.get_meals(&options);
+
+            let meals: Vec<ExpandedMeal> = meals.iter().map(|meal| ExpandedMeal::new(&state, meal)).collect();
+
+            Ok(list_response(tagged(HttpResponse::Ok(), version, format), format, total, &meals))
+        },
+        Err(err_id) => {
+            Ok(
+                HttpResponse::UnprocessableEntity()
+                    .content_type(ContentType::json())
+                    .json(err_id)
+            )
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "meals",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Matching meals, best match first", body = Vec<MealSearchResult>),
+        (status = 422, description = "Missing or empty query (-1)", body = i32)
     )
+)]
+#[get("/meals/search")]
+pub async fn search_meals(_auth: Authorized<Reader>, query: Query<SearchQuery>, format: Format, state: CurrentTenant) -> HttpResponse {
+    match query.get_query() {
+        Some(q) => reply(HttpResponse::Ok(), format, &state.search_meals(q, query.get_limit())),
+        None => {
+            HttpResponse::UnprocessableEntity()
+                .content_type(ContentType::json())
+                .json(-1)
+        }
+    }
 }
 
+#[utoipa::path(
+    tag = "meals",
+    responses(
+        (status = 405, description = "Deleting every meal is not supported", body = String)
+    )
+)]
 #[delete("/meals")]
 pub async fn delete_meals() -> HttpResponse {
     HttpResponse::MethodNotAllowed()
@@ -91,9 +150,19 @@ pub async fn delete_meals() -> HttpResponse {
         .json(String::from("Not implemented"))
 }
 
+#[utoipa::path(
+    tag = "meals",
+    params(("name" = String, Path, description = "ID of the meal, or its name if it doesn't parse as an integer"), ExpandParams),
+    responses(
+        (status = 200, description = "The meal, as ExpandedMeal with expand=dishes", body = Meal, headers(("ETag" = String, description = "Version of the meal, and of the dish collection with expand=dishes"))),
+        (status = 304, description = "The meal hasn't changed since the version given in If-None-Match"),
+        (status = 404, description = "Meal not found (-5)", body = i32),
+        (status = 422, description = "Invalid expand parameter (-1)", body = i32)
+    )
+)]
 #[get("/meals/{name}")]
-pub async fn get_meal(path: Path<String>, state: Data<AppState>) -> Result<HttpResponse, Error> {
-    let name = path.into_inner();
+pub async fn get_meal(_auth: Authorized<Reader>, req: HttpRequest, path: Path<String>, format: Format, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    let name = decode_name(path.into_inner());
 
     let meal: Result<Meal, i32>;
 
@@ -102,14 +171,68 @@ pub async fn get_meal(path: Path<String>, state: Data<AppState>) -> Result<HttpR
         Err(_) => meal = state.get_meal_by_name(&name)
     }
 
-    match meal {
-        Ok(data) => {
+    meal_response(&req, format, &state, meal)
+}
+
+#[utoipa::path(
+    tag = "meals",
+    params(("id" = i32, Path, description = "ID of the meal"), ExpandParams),
+    responses(
+        (status = 200, description = "The meal, as ExpandedMeal with expand=dishes", body = Meal, headers(("ETag" = String, description = "Version of the meal, and of the dish collection with expand=dishes"))),
+        (status = 304, description = "The meal hasn't changed since the version given in If-None-Match"),
+        (status = 404, description = "Meal not found (-5)", body = i32),
+        (status = 422, description = "Invalid expand parameter (-1)", body = i32)
+    )
+)]
+#[get("/meals/id/{id}")]
+pub async fn get_meal_with_id(_auth: Authorized<Reader>, req: HttpRequest, path: Path<i32>, format: Format, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    meal_response(&req, format, &state, state.get_meal_by_id(path.into_inner()))
+}
+
+#[utoipa::path(
+    tag = "meals",
+    params(("name" = String, Path, description = "Name of the meal, percent-encoded"), ExpandParams),
+    responses(
+        (status = 200, description = "The meal, as ExpandedMeal with expand=dishes", body = Meal, headers(("ETag" = String, description = "Version of the meal, and of the dish collection with expand=dishes"))),
+        (status = 304, description = "The meal hasn't changed since the version given in If-None-Match"),
+        (status = 404, description = "Meal not found (-5)", body = i32),
+        (status = 422, description = "Invalid expand parameter (-1)", body = i32)
+    )
+)]
+#[get("/meals/name/{name:.*}")]
+pub async fn get_meal_with_name(_auth: Authorized<Reader>, req: HttpRequest, path: Path<String>, format: Format, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    meal_response(&req, format, &state, state.get_meal_by_name(&decode_name(path.into_inner())))
+}
+
+fn meal_response(req: &HttpRequest, format: Format, state: &Tenant, meal: Result<Meal, i32>) -> Result<HttpResponse, Error> {
+    let expand = match parse_expand(req.query_string()) {
+        Ok(expand) => expand,
+        Err(err_id) => {
             return Ok(
-                HttpResponse::Ok()
+                HttpResponse::UnprocessableEntity()
                     .content_type(ContentType::json())
-                    .json(data)
+                    .json(err_id)
             )
         }
+    };
+
+    match meal {
+        Ok(data) if expand => {
+            let version = (data.version(), state.get_dishes_version());
+
+            if let Some(res) = not_modified(req, version, format) {
+                return Ok(res)
+            }
+
+            return Ok(reply(tagged(HttpResponse::Ok(), version, format), format, &ExpandedMeal::new(state, &data)))
+        },
+        Ok(data) => {
+            if let Some(res) = not_modified(req, data.version(), format) {
+                return Ok(res)
+            }
+
+            return Ok(reply(tagged(HttpResponse::Ok(), data.version(), format), format, &data))
+        }
         _ => {
             return Ok(
                 HttpResponse::NotFound()
@@ -120,9 +243,17 @@ pub async fn get_meal(path: Path<String>, state: Data<AppState>) -> Result<HttpR
     }
 }
 
+#[utoipa::path(
+    tag = "meals",
+    params(("name" = String, Path, description = "ID of the meal, or its name if it doesn't parse as an integer")),
+    responses(
+        (status = 200, description = "ID of the deleted meal", body = i32),
+        (status = 404, description = "Meal not found (-5)", body = i32)
+    )
+)]
 #[delete("/meals/{name}")]
-pub async fn delete_meal(path: Path<String>, state: Data<AppState>) -> Result<HttpResponse, Error> {
-    let name = path.into_inner();
+pub async fn delete_meal(_auth: Authorized<Editor>, path: Path<String>, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    let name = decode_name(path.into_inner());
 
     let res: Result<i32, i32>;
 
@@ -131,6 +262,36 @@ pub async fn delete_meal(path: Path<String>, state: Data<AppState>) -> Result<Ht
         Err(_) => res = state.delete_meal_by_name(&name)
     }
 
+    delete_meal_response(res)
+}
+
+#[utoipa::path(
+    tag = "meals",
+    params(("id" = i32, Path, description = "ID of the meal")),
+    responses(
+        (status = 200, description = "ID of the deleted meal", body = i32),
+        (status = 404, description = "Meal not found (-5)", body = i32)
+    )
+)]
+#[delete("/meals/id/{id}")]
+pub async fn delete_meal_with_id(_auth: Authorized<Editor>, path: Path<i32>, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    delete_meal_response(state.delete_meal_by_id(&path.into_inner()))
+}
+
+#[utoipa::path(
+    tag = "meals",
+    params(("name" = String, Path, description = "Name of the meal, percent-encoded")),
+    responses(
+        (status = 200, description = "ID of the deleted meal", body = i32),
+        (status = 404, description = "Meal not found (-5)", body = i32)
+    )
+)]
+#[delete("/meals/name/{name:.*}")]
+pub async fn delete_meal_with_name(_auth: Authorized<Editor>, path: Path<String>, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    delete_meal_response(state.delete_meal_by_name(&decode_name(path.into_inner())))
+}
+
+fn delete_meal_response(res: Result<i32, i32>) -> Result<HttpResponse, Error> {
     match res {
         Ok(id) => {
             return Ok(
@@ -149,48 +310,44 @@ pub async fn delete_meal(path: Path<String>, state: Data<AppState>) -> Result<Ht
     }
 }
 
+#[utoipa::path(
+    tag = "meals",
+    params(("ID" = i32, Path, description = "ID of the meal")),
+    request_body = CreateMealRequest,
+    responses(
+        (status = 200, description = "ID of the updated meal", body = i32, headers(("ETag" = String, description = "New version of the meal"))),
+        (status = 404, description = "Meal not found (-5), another meal already has the name (-2) or unknown dish ID (-6)", body = i32),
+        (status = 412, description = "The meal changed since the version given in If-Match (-9)", body = i32),
+        (status = 413, description = "Request body is too large (-8)", body = i32),
+        (status = 415, description = "Content-Type is not JSON, YAML or MessagePack (0)", body = i32),
+        (status = 422, description = "Missing parameter (-1)", body = i32)
+    )
+)]
 #[put("/meals/{ID}")]
-pub async fn update_meal(path: Path<i32>, content_type: Header<ContentType>, mut payload: Payload, state: Data<AppState>) -> Result<HttpResponse, Error> {
-    if content_type.to_string() != String::from("application/json") {
-        return Ok(
-            HttpResponse::UnsupportedMediaType()
-                .content_type(ContentType::json())
-                .json(0)
-        )
-    }
+pub async fn update_meal(_auth: Authorized<Editor>, req: HttpRequest, path: Path<i32>, data: Body<CreateMealRequest>, state: CurrentTenant) -> Result<HttpResponse, Error> {
+    let data = data.into_inner();
 
-    let data: CreateMealRequest;
-    let mut body = BytesMut::new();
-
-    while let Some(chunk) = payload.next().await {
-        let chunk = chunk?;
-
-        body.extend_from_slice(&chunk);
-    }
-
-    match serde_json::from_slice::<CreateMealRequest>(&body) {
-        Ok(body) => data = body,
-        _ => {
-            return Ok(
-                HttpResponse::UnprocessableEntity()
-                    .content_type(ContentType::json())
-                    .json(-1)
-            )
-        }
-    }
-    
     let meal_id: i32 = path.into_inner();
 
-    let res = state.update_meal(&meal_id, &data.name, &data.appetizer, &data.main, &data.dessert);
+    let res = state.update_meal(&meal_id, &data.name, &data.appetizer, &data.main, &data.dessert, if_match(&req));
 
     match res {
         Ok(id) => {
+            let version = state.get_meal_by_id(id).map(|meal| meal.version()).unwrap_or_default();
+
             Ok(
-                HttpResponse::Ok()
+                tagged(HttpResponse::Ok(), version, Format::Json)
                     .content_type(ContentType::json())
                     .json(id)
             )
         },
+        Err(-9) => {
+            Ok(
+                HttpResponse::PreconditionFailed()
+                    .content_type(ContentType::json())
+                    .json(-9)
+            )
+        },
         Err(err_id) => {
             Ok(
                 HttpResponse::NotFound()
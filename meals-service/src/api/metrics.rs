@@ -0,0 +1,64 @@
+use std::time::Instant;
+
+use actix_web::{
+    get,
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{Method, StatusCode, header},
+    middleware::Next,
+    web::Data,
+    Error,
+    HttpResponse
+};
+
+use crate::repository::state::AppState;
+
+// Counts every request and how long it took by method, route pattern and status, including the
+// ones rejected by the other middleware
+pub async fn measure(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, Error> {
+    let state = req.app_data::<Data<AppState>>().cloned();
+
+    let state = match state {
+        Some(state) => state,
+        None => return next.call(req).await.map(ServiceResponse::map_into_boxed_body)
+    };
+
+    let started = Instant::now();
+    let method = req.method().to_string();
+    // Requests that match no route share one series rather than adding one per path
+    let route = req.match_pattern().unwrap_or_else(|| String::from("unmatched"));
+    let revalidating = req.method() == Method::GET && req.headers().contains_key(header::IF_NONE_MATCH);
+
+    let res = next.call(req).await;
+
+    let status = match &res {
+        Ok(res) => res.status(),
+        Err(err) => err.as_response_error().status_code()
+    };
+
+    state.metrics().record_request(&method, &route, status.as_u16(), started.elapsed());
+
+    if revalidating && (status == StatusCode::NOT_MODIFIED || status == StatusCode::OK) {
+        state.metrics().record_revalidation(status == StatusCode::NOT_MODIFIED);
+    }
+
+    res.map(ServiceResponse::map_into_boxed_body)
+}
+
+#[utoipa::path(
+    tag = "metrics",
+    responses(
+        (status = 200, description = "Request counts and latencies, nutrition API calls, the ETag hit ratio and the number of dishes and meals, in the Prometheus text format", body = String, content_type = "text/plain")
+    )
+)]
+#[get("/metrics")]
+pub async fn get_metrics(state: Data<AppState>) -> HttpResponse {
+    let tenants = state.get_tenants();
+
+    let dishes = tenants.iter().map(|tenant| tenant.dishes).sum();
+    let meals = tenants.iter().map(|tenant| tenant.meals).sum();
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(state.metrics().render(dishes, meals))
+}
@@ -1,2 +1,197 @@
 pub mod dish;
-pub mod meal;
\ No newline at end of file
+pub mod meal;
+pub mod path;
+pub mod search;
+pub mod list;
+pub mod openapi;
+pub mod representation;
+pub mod body;
+pub mod conditional;
+pub mod expand;
+pub mod idempotency;
+pub mod auth;
+pub mod events;
+pub mod webhook;
+pub mod graphql;
+pub mod key;
+pub mod tenant;
+pub mod metrics;
+pub mod request_id;
+pub mod trace;
+pub mod health;
+pub mod v2;
+
+use actix_web::{
+    dev::HttpServiceFactory,
+    web::{Data, ServiceConfig}
+};
+
+use utoipa::{Path, openapi::path::HttpMethod};
+
+use utoipa_rapidoc::RapiDoc;
+
+use dish::{
+    get_dishes,
+    create_dish,
+    create_dishes,
+    update_dish,
+    delete_dishes,
+    search_dishes,
+    get_dish,
+    get_dish_with_id,
+    get_dish_with_name,
+    delete_dish,
+    delete_dish_with_id,
+    delete_dish_with_name
+};
+
+use meal::{
+    create_meal,
+    get_meals,
+    delete_meals,
+    search_meals,
+    get_meal,
+    get_meal_with_id,
+    get_meal_with_name,
+    delete_meal,
+    delete_meal_with_id,
+    delete_meal_with_name,
+    update_meal
+};
+
+use events::get_events;
+
+use webhook::{
+    create_webhook,
+    get_webhooks,
+    get_webhook,
+    delete_webhook,
+    get_webhook_deliveries,
+    ping_webhook
+};
+
+use graphql::{post_graphql, get_graphiql};
+
+use key::{create_key, get_keys, delete_key};
+
+use tenant::{get_tenants, get_tenant, create_tenant, update_quota};
+
+use metrics::get_metrics;
+
+use health::{get_liveness, get_readiness, get_dependencies};
+
+use openapi::get_openapi;
+
+// Method and path of a documented route, as given in #[utoipa::path]
+pub type Operation = (HttpMethod, String);
+
+// Shared by main and the OpenAPI test so both see the same routes
+pub fn configure(cfg: &mut ServiceConfig) {
+    routes(cfg, &mut Vec::new());
+}
+
+// Adds every route, noting the operations of the documented ones so the OpenAPI test can check
+// them against the document
+pub fn routes(cfg: &mut ServiceConfig, operations: &mut Vec<Operation>) {
+    cfg
+        .app_data(body::payload_config())
+        .app_data(Data::new(graphql::schema()));
+
+    route(cfg, operations, get_dishes);
+    route(cfg, operations, create_dish);
+    route(cfg, operations, create_dishes);
+    route(cfg, operations, delete_dishes);
+    route(cfg, operations, search_dishes); // Must be registered before get_dish so "search" isn't read as a name
+    route(cfg, operations, get_dish);
+    route(cfg, operations, get_dish_with_id);
+    route(cfg, operations, get_dish_with_name);
+    route(cfg, operations, delete_dish);
+    route(cfg, operations, delete_dish_with_id);
+    route(cfg, operations, delete_dish_with_name);
+    route(cfg, operations, update_dish);
+    route(cfg, operations, create_meal);
+    route(cfg, operations, get_meals);
+    route(cfg, operations, delete_meals);
+    route(cfg, operations, search_meals); // Likewise before get_meal
+    route(cfg, operations, get_meal);
+    route(cfg, operations, get_meal_with_id);
+    route(cfg, operations, get_meal_with_name);
+    route(cfg, operations, delete_meal);
+    route(cfg, operations, delete_meal_with_id);
+    route(cfg, operations, delete_meal_with_name);
+    route(cfg, operations, update_meal);
+    route(cfg, operations, get_events);
+    route(cfg, operations, create_webhook);
+    route(cfg, operations, get_webhooks);
+    route(cfg, operations, get_webhook);
+    route(cfg, operations, delete_webhook);
+    route(cfg, operations, get_webhook_deliveries);
+    route(cfg, operations, ping_webhook);
+    route(cfg, operations, post_graphql);
+    route(cfg, operations, get_graphiql);
+    route(cfg, operations, create_key);
+    route(cfg, operations, get_keys);
+    route(cfg, operations, delete_key);
+    route(cfg, operations, get_tenants);
+    route(cfg, operations, get_tenant);
+    route(cfg, operations, create_tenant);
+    route(cfg, operations, update_quota);
+    route(cfg, operations, get_metrics);
+    route(cfg, operations, get_liveness);
+    route(cfg, operations, get_readiness);
+    route(cfg, operations, get_dependencies);
+
+    v2::configure(cfg, operations);
+
+    // The document itself and the page rendering it aren't part of it
+    cfg
+        .service(get_openapi)
+        .service(RapiDoc::new("/openapi.json").path("/docs"));
+}
+
+// Adds a documented handler
+pub fn route<H: HttpServiceFactory + Path + 'static>(cfg: &mut ServiceConfig, operations: &mut Vec<Operation>, handler: H) {
+    operations.extend(H::methods().into_iter().map(|method| (method, H::path())));
+
+    cfg.service(handler);
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        test,
+        web::Data,
+        App,
+        http::StatusCode
+    };
+
+    use crate::repository::{state::AppState, tenants::DEFAULT_TENANT};
+
+    use super::configure;
+
+    async fn get(uri: &str) -> (StatusCode, String) {
+        let state = AppState::new();
+
+        state.create_tenant(DEFAULT_TENANT);
+
+        let app = test::init_service(App::new().app_data(Data::new(state)).configure(configure)).await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        let status = res.status();
+
+        (status, String::from_utf8(test::read_body(res).await.to_vec()).unwrap())
+    }
+
+    #[actix_web::test]
+    async fn search_is_not_read_as_a_name() {
+        assert_eq!(get("/dishes/search?q=pa").await, (StatusCode::OK, String::from("[]")));
+        assert_eq!(get("/meals/search?q=pa").await, (StatusCode::OK, String::from("[]")));
+        assert_eq!(get("/dishes/search").await, (StatusCode::UNPROCESSABLE_ENTITY, String::from("-1")));
+    }
+
+    #[actix_web::test]
+    async fn dishes_and_meals_named_search_are_found_by_name() {
+        assert_eq!(get("/dishes/name/search").await, (StatusCode::NOT_FOUND, String::from("-5")));
+        assert_eq!(get("/meals/name/search").await, (StatusCode::NOT_FOUND, String::from("-5")));
+    }
+}
@@ -0,0 +1,258 @@
+use actix_web::{
+    get,
+    HttpResponse,
+    http::header::ContentType
+};
+
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify,
+    OpenApi
+};
+
+use crate::{
+    repository::{
+        state::{Dish, DishItem, Meal},
+        nutrition_api_client::MultiItemPolicy,
+        search::MatchType,
+        webhooks::{Subscription, Delivery},
+        api_keys::{self, Role},
+        tenants::{Quota, TenantSummary},
+        health::{Readiness, Status, DependencyHealth, NutritionApiHealth, StorageHealth},
+        circuit_breaker::BreakerState
+    },
+    api::{dish, meal, expand, events, webhook, graphql, key, tenant, metrics, health, v2}
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Meals Service", description = "Computes and stores nutritional data for dishes and meals"),
+    paths(
+        dish::get_dishes,
+        dish::create_dish,
+        dish::create_dishes,
+        dish::search_dishes,
+        dish::delete_dishes,
+        dish::get_dish,
+        dish::get_dish_with_id,
+        dish::get_dish_with_name,
+        dish::delete_dish,
+        dish::delete_dish_with_id,
+        dish::delete_dish_with_name,
+        dish::update_dish,
+        meal::create_meal,
+        meal::get_meals,
+        meal::search_meals,
+        meal::delete_meals,
+        meal::get_meal,
+        meal::get_meal_with_id,
+        meal::get_meal_with_name,
+        meal::delete_meal,
+        meal::delete_meal_with_id,
+        meal::delete_meal_with_name,
+        meal::update_meal,
+        v2::dish::get_dishes,
+        v2::dish::create_dish,
+        v2::dish::get_dish,
+        v2::dish::get_dish_with_name,
+        v2::dish::delete_dish,
+        v2::dish::delete_dish_with_name,
+        v2::dish::update_dish,
+        v2::meal::get_meals,
+        v2::meal::create_meal,
+        v2::meal::get_meal,
+        v2::meal::get_meal_with_name,
+        v2::meal::delete_meal,
+        v2::meal::delete_meal_with_name,
+        v2::meal::update_meal,
+        events::get_events,
+        webhook::create_webhook,
+        webhook::get_webhooks,
+        webhook::get_webhook,
+        webhook::delete_webhook,
+        webhook::get_webhook_deliveries,
+        webhook::ping_webhook,
+        graphql::post_graphql,
+        graphql::get_graphiql,
+        key::create_key,
+        key::get_keys,
+        key::delete_key,
+        tenant::get_tenants,
+        tenant::get_tenant,
+        tenant::create_tenant,
+        tenant::update_quota,
+        metrics::get_metrics,
+        health::get_liveness,
+        health::get_readiness,
+        health::get_dependencies
+    ),
+    components(schemas(
+        Dish,
+        DishItem,
+        Meal,
+        MatchType,
+        MultiItemPolicy,
+        dish::CreateDishRequest,
+        dish::BatchResult,
+        meal::CreateMealRequest,
+        expand::ExpandedMeal,
+        expand::Course,
+        expand::Contribution,
+        webhook::CreateWebhookRequest,
+        Subscription,
+        Delivery,
+        api_keys::ApiKey,
+        Role,
+        key::CreateKeyRequest,
+        Quota,
+        TenantSummary,
+        Readiness,
+        Status,
+        DependencyHealth,
+        NutritionApiHealth,
+        BreakerState,
+        StorageHealth,
+        v2::ApiError,
+        v2::DishPage,
+        v2::MealPage
+    )),
+    tags(
+        (name = "dishes", description = "Dishes with nutrition data from the API Ninjas Nutrition API"),
+        (name = "meals", description = "Meals made of an appetizer, main and dessert dish"),
+        (name = "v2", description = "Dishes and meals with full resource bodies and JSON error documents"),
+        (name = "events", description = "Server-Sent Events for changes to dishes and meals"),
+        (name = "webhooks", description = "Signed notifications of changes to dishes and meals sent to subscribed URLs"),
+        (name = "graphql", description = "GraphQL schema over the same dishes and meals"),
+        (name = "keys", description = "API keys and the roles they grant"),
+        (name = "tenants", description = "Admin view of the tenants sharing the deployment, and their quotas"),
+        (name = "metrics", description = "Prometheus metrics"),
+        (name = "health", description = "Liveness, readiness and the state of the nutrition API and storage")
+    ),
+    modifiers(&Security)
+)]
+pub struct ApiDoc;
+
+// Both ways of sending an API key. They are only required once a key has been configured
+struct Security;
+
+impl Modify for Security {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+
+        components.add_security_scheme("api_key", SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))));
+        components.add_security_scheme("bearer", SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()));
+    }
+}
+
+#[get("/openapi.json")]
+pub async fn get_openapi() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::json())
+        .json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        test,
+        web::{self, Data},
+        App,
+        HttpResponse,
+        http::{Method, StatusCode}
+    };
+
+    use utoipa::{OpenApi, openapi::path::HttpMethod};
+
+    use crate::{api::{configure, routes}, repository::state::AppState};
+
+    use super::ApiDoc;
+
+    // Answered only when no handler matches the path and method of a request
+    const UNROUTED: u16 = 599;
+
+    // Calling these would reach the nutrition API or open a stream, so they are only checked
+    // against the routes registered
+    const NOT_CALLED: [&str; 2] = ["/health/dependencies", "/events"];
+
+    #[actix_web::test]
+    async fn every_documented_operation_has_a_handler() {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppState::new()))
+                .configure(configure)
+                .default_service(web::to(|| async { HttpResponse::new(StatusCode::from_u16(UNROUTED).unwrap()) }))
+        ).await;
+
+        let spec = ApiDoc::openapi();
+
+        assert!(!spec.paths.paths.is_empty());
+
+        for (path, item) in spec.paths.paths.iter().filter(|(path, _)| !NOT_CALLED.contains(&path.as_str())) {
+            let uri = path
+                .replace("{name}", "pasta")
+                .replace("{id}", "1")
+                .replace("{ID}", "1");
+
+            let operations = [
+                (Method::GET, &item.get),
+                (Method::POST, &item.post),
+                (Method::PUT, &item.put),
+                (Method::DELETE, &item.delete),
+                (Method::PATCH, &item.patch)
+            ];
+
+            for (method, _) in operations.into_iter().filter(|(_, operation)| operation.is_some()) {
+                let req = test::TestRequest::default()
+                    .method(method.clone())
+                    .uri(&uri)
+                    .insert_header(("Content-Type", "application/json"))
+                    .set_payload("{}")
+                    .to_request();
+
+                let res = test::call_service(&app, req).await;
+
+                assert_ne!(res.status().as_u16(), UNROUTED, "{} {} is documented but has no handler", method, path);
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn every_handler_is_documented() {
+        let mut operations = Vec::new();
+
+        let _ = App::new().configure(|cfg| routes(cfg, &mut operations));
+
+        let spec = ApiDoc::openapi();
+
+        assert!(!operations.is_empty());
+
+        for (method, path) in operations {
+            let item = spec.paths.paths.get(&path);
+
+            let (name, documented) = match method {
+                HttpMethod::Get => ("GET", item.is_some_and(|item| item.get.is_some())),
+                HttpMethod::Post => ("POST", item.is_some_and(|item| item.post.is_some())),
+                HttpMethod::Put => ("PUT", item.is_some_and(|item| item.put.is_some())),
+                HttpMethod::Delete => ("DELETE", item.is_some_and(|item| item.delete.is_some())),
+                HttpMethod::Patch => ("PATCH", item.is_some_and(|item| item.patch.is_some())),
+                _ => ("other", false)
+            };
+
+            assert!(documented, "{} {} has a handler but is not documented", name, path);
+        }
+    }
+
+    #[actix_web::test]
+    async fn openapi_document_is_served() {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppState::new()))
+                .configure(configure)
+        ).await;
+
+        let req = test::TestRequest::get().uri("/openapi.json").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(body, serde_json::to_value(ApiDoc::openapi()).unwrap());
+    }
+}
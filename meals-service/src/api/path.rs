@@ -0,0 +1,56 @@
+use percent_encoding::percent_decode_str;
+
+// Actix leaves "%2F", "%25" and "%2B" encoded when extracting path parameters so that
+// they can't be confused with route separators, this finishes decoding names like "salt%2Fpepper"
+pub fn decode_name(raw: String) -> String {
+    percent_decode_str(&raw).decode_utf8_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        get,
+        test::{init_service, call_and_read_body, TestRequest},
+        web::Path,
+        App
+    };
+
+    use super::decode_name;
+
+    #[get("/dishes/name/{name}")]
+    async fn echo(name: Path<String>) -> String {
+        decode_name(name.into_inner())
+    }
+
+    async fn extract(uri: &str) -> String {
+        let app = init_service(App::new().service(echo)).await;
+
+        String::from_utf8(call_and_read_body(&app, TestRequest::get().uri(uri).to_request()).await.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn leaves_plain_names_alone() {
+        assert_eq!(decode_name(String::from("chicken soup")), "chicken soup");
+        assert_eq!(decode_name(String::from("100% rye")), "100% rye");
+    }
+
+    #[test]
+    fn decodes_what_actix_leaves_encoded() {
+        assert_eq!(decode_name(String::from("salt%2Fpepper")), "salt/pepper");
+        assert_eq!(decode_name(String::from("salt%2fpepper")), "salt/pepper");
+        assert_eq!(decode_name(String::from("mac %2B cheese")), "mac + cheese");
+        assert_eq!(decode_name(String::from("100%25 rye")), "100% rye");
+    }
+
+    #[test]
+    fn replaces_invalid_utf_8() {
+        assert_eq!(decode_name(String::from("caf%FF")), "caf\u{FFFD}");
+    }
+
+    #[actix_web::test]
+    async fn names_from_the_path_are_decoded_once() {
+        assert_eq!(extract("/dishes/name/salt%2Fpepper").await, "salt/pepper");
+        assert_eq!(extract("/dishes/name/mac%20%2B%20cheese").await, "mac + cheese");
+        assert_eq!(extract("/dishes/name/cr%C3%A8me%20br%C3%BBl%C3%A9e").await, "crème brûlée");
+    }
+}
@@ -0,0 +1,293 @@
+use std::future::{ready, Ready};
+
+use actix_web::{
+    dev::Payload,
+    error::InternalError,
+    http::header::{self, Accept, ContentType, Header},
+    FromRequest,
+    HttpRequest,
+    HttpResponse,
+    HttpResponseBuilder
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use serde_json::{Map, Value};
+
+use mime::Mime;
+
+const JSON_TYPES: [&str; 1] = ["application/json"];
+const YAML_TYPES: [&str; 3] = ["application/yaml", "application/x-yaml", "text/yaml"];
+const MESSAGE_PACK_TYPES: [&str; 3] = ["application/msgpack", "application/x-msgpack", "application/vnd.msgpack"];
+const CSV_TYPES: [&str; 1] = ["text/csv"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Yaml,
+    MessagePack,
+    Csv
+}
+
+impl Format {
+    // Short name of the format, used in ETags
+    pub fn token(&self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+            Format::MessagePack => "msgpack",
+            Format::Csv => "csv"
+        }
+    }
+
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Format::Json => JSON_TYPES[0],
+            Format::Yaml => YAML_TYPES[0],
+            Format::MessagePack => MESSAGE_PACK_TYPES[0],
+            Format::Csv => CSV_TYPES[0]
+        }
+    }
+
+    // Matches exact media types as well as structured syntax suffixes, so application/problem+json
+    // is read as JSON and application/vnd.menu+yaml as YAML
+    fn from_mime(mime: &Mime) -> Option<Format> {
+        let essence = mime.essence_str().to_lowercase();
+        let suffix = mime.suffix().map(|suffix| suffix.as_str().to_lowercase());
+
+        if JSON_TYPES.contains(&essence.as_str()) || suffix.as_deref() == Some("json") {
+            Some(Format::Json)
+        } else if YAML_TYPES.contains(&essence.as_str()) || suffix.as_deref() == Some("yaml") {
+            Some(Format::Yaml)
+        } else if MESSAGE_PACK_TYPES.contains(&essence.as_str()) || suffix.as_deref() == Some("msgpack") {
+            Some(Format::MessagePack)
+        } else if CSV_TYPES.contains(&essence.as_str()) {
+            Some(Format::Csv)
+        } else {
+            None
+        }
+    }
+
+    // Picks the representation for a response from the Accept header, preferring JSON when the
+    // client accepts anything. Returns None when none of the accepted types can be produced
+    pub fn from_accept(req: &HttpRequest) -> Option<Format> {
+        let accept = match Accept::parse(req) {
+            Ok(accept) if !accept.is_empty() => accept,
+            _ => return Some(Format::Json)
+        };
+
+        for mime in accept.ranked() {
+            if mime.type_() == mime::STAR || (mime.type_() == mime::APPLICATION && mime.subtype() == mime::STAR) {
+                return Some(Format::Json)
+            }
+
+            if let Some(format) = Format::from_mime(&mime) {
+                return Some(format)
+            }
+        }
+
+        None
+    }
+
+    // Request bodies can be sent as JSON, YAML or MessagePack. Parameters are allowed, but text
+    // formats have to be UTF-8, so "application/json; charset=utf-8" is accepted and
+    // "application/json; charset=latin1" isn't
+    pub fn from_content_type(req: &HttpRequest) -> Option<Format> {
+        let mime: Mime = req.headers().get(header::CONTENT_TYPE)?.to_str().ok()?.parse().ok()?;
+
+        let format = match Format::from_mime(&mime) {
+            Some(Format::Csv) | None => return None,
+            Some(format) => format
+        };
+
+        match mime.get_param(mime::CHARSET) {
+            Some(charset) if format != Format::MessagePack && !["utf-8", "utf8"].contains(&charset.as_str().to_lowercase().as_str()) => None,
+            _ => Some(format)
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, body: &[u8]) -> Option<T> {
+        match self {
+            Format::Json => serde_json::from_slice(body).ok(),
+            Format::Yaml => serde_yaml::from_slice(body).ok(),
+            Format::MessagePack => rmp_serde::from_slice(body).ok(),
+            Format::Csv => None
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> Option<Vec<u8>> {
+        match self {
+            Format::Json => serde_json::to_vec(value).ok(),
+            Format::Yaml => serde_yaml::to_string(value).ok().map(String::into_bytes),
+            Format::MessagePack => rmp_serde::to_vec_named(value).ok(),
+            // Round trip through JSON text so f32 fields keep their short form (251.4 rather than 251.39999389648438)
+            Format::Csv => to_csv(&serde_json::from_slice(&serde_json::to_vec(value).ok()?).ok()?)
+        }
+    }
+}
+
+impl FromRequest for Format {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Format, actix_web::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        match Format::from_accept(req) {
+            Some(format) => ready(Ok(format)),
+            None => {
+                let supported = [JSON_TYPES[0], YAML_TYPES[0], MESSAGE_PACK_TYPES[0], CSV_TYPES[0]];
+
+                let res = HttpResponse::NotAcceptable()
+                    .content_type(ContentType::json())
+                    .json(supported);
+
+                ready(Err(InternalError::from_response("Not Acceptable", res).into()))
+            }
+        }
+    }
+}
+
+pub fn reply<T: Serialize>(mut builder: HttpResponseBuilder, format: Format, value: &T) -> HttpResponse {
+    match format.encode(value) {
+        Some(body) => builder.content_type(format.media_type()).body(body),
+        None => HttpResponse::InternalServerError().finish()
+    }
+}
+
+// Collections (arrays, or objects of objects keyed by ID) become one row per item and anything
+// else becomes a single row. Columns are the union of the item fields, and nested values are
+// written as JSON so that e.g. the items of a dish fit in one cell
+fn to_csv(value: &Value) -> Option<Vec<u8>> {
+    let rows: Vec<Map<String, Value>> = match value {
+        Value::Array(items) => items.iter().map(to_row).collect(),
+        Value::Object(items) if !items.is_empty() && items.values().all(Value::is_object) => items.values().map(to_row).collect(),
+        Value::Object(items) if items.is_empty() => Vec::new(),
+        other => vec![to_row(other)]
+    };
+
+    let mut columns: Vec<&String> = Vec::new();
+
+    for row in rows.iter() {
+        for key in row.keys() {
+            if !columns.contains(&key) {
+                columns.push(key);
+            }
+        }
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+
+    if !columns.is_empty() {
+        writer.write_record(columns.iter().map(|column| column.as_str())).ok()?;
+    }
+
+    for row in rows.iter() {
+        let record: Vec<String> = columns.iter().map(|column| match row.get(*column) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Null) | None => String::new(),
+            Some(other) => other.to_string()
+        }).collect();
+
+        writer.write_record(record).ok()?;
+    }
+
+    writer.into_inner().ok()
+}
+
+fn to_row(value: &Value) -> Map<String, Value> {
+    match value {
+        Value::Object(fields) => fields.clone(),
+        other => {
+            let mut row = Map::new();
+
+            row.insert(String::from("value"), other.clone());
+
+            row
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        test::TestRequest,
+        http::StatusCode,
+        FromRequest
+    };
+
+    use serde::Serialize;
+
+    use serde_json::json;
+
+    use super::Format;
+
+    fn negotiate(accept: &str) -> Option<Format> {
+        Format::from_accept(&TestRequest::get().insert_header(("Accept", accept)).to_http_request())
+    }
+
+    #[test]
+    fn defaults_to_json() {
+        assert_eq!(Format::from_accept(&TestRequest::get().to_http_request()), Some(Format::Json));
+        assert_eq!(negotiate("*/*"), Some(Format::Json));
+        assert_eq!(negotiate("application/*"), Some(Format::Json));
+    }
+
+    #[test]
+    fn picks_the_highest_q_value() {
+        assert_eq!(negotiate("application/json;q=0.5, text/csv"), Some(Format::Csv));
+        assert_eq!(negotiate("text/csv;q=0.2, application/yaml;q=0.9, application/msgpack;q=0.5"), Some(Format::Yaml));
+        assert_eq!(negotiate("text/html, application/vnd.msgpack;q=0.1"), Some(Format::MessagePack));
+    }
+
+    #[test]
+    fn prefers_a_named_type_over_a_wildcard_of_the_same_rank() {
+        assert_eq!(negotiate("*/*;q=0.8, text/csv"), Some(Format::Csv));
+        assert_eq!(negotiate("text/csv;q=0.5, */*"), Some(Format::Json));
+    }
+
+    #[test]
+    fn finds_nothing_for_unsupported_types() {
+        assert_eq!(negotiate("text/html"), None);
+        assert_eq!(negotiate("image/png, text/*"), None);
+    }
+
+    #[actix_web::test]
+    async fn answers_unsupported_types_with_406() {
+        let req = TestRequest::get().insert_header(("Accept", "text/html")).to_http_request();
+
+        let err = Format::from_request(&req, &mut actix_web::dev::Payload::None).await.unwrap_err();
+
+        assert_eq!(err.as_response_error().status_code(), StatusCode::NOT_ACCEPTABLE);
+    }
+
+    #[test]
+    fn writes_collections_as_one_csv_row_per_item() {
+        let dishes = json!({
+            "1": {"ID": 1, "name": "pasta", "cal": 500.0, "items": [{"name": "pasta"}]},
+            "2": {"ID": 2, "name": "salad, green", "cal": 28.2, "extra": null}
+        });
+
+        let csv = String::from_utf8(Format::Csv.encode(&dishes).unwrap()).unwrap();
+
+        assert_eq!(csv, "ID,name,cal,items,extra\n1,pasta,500.0,\"[{\"\"name\"\":\"\"pasta\"\"}]\",\n2,\"salad, green\",28.2,,\n");
+    }
+
+    #[test]
+    fn writes_single_values_as_one_csv_row() {
+        assert_eq!(String::from_utf8(Format::Csv.encode(&3).unwrap()).unwrap(), "value\n3\n");
+        assert_eq!(Format::Csv.encode(&json!({})), Some(Vec::new()));
+    }
+
+    #[derive(Serialize)]
+    struct Item {
+        name: &'static str,
+        cal: f32
+    }
+
+    #[test]
+    fn keeps_the_short_form_of_f32_fields() {
+        let item = Item { name: "focaccia", cal: 251.4 };
+
+        assert_eq!(String::from_utf8(Format::Yaml.encode(&item).unwrap()).unwrap(), "name: focaccia\ncal: 251.4\n");
+        assert_eq!(String::from_utf8(Format::Csv.encode(&[item]).unwrap()).unwrap(), "name,cal\nfocaccia,251.4\n");
+    }
+}
@@ -0,0 +1,73 @@
+use std::time::Instant;
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    Error
+};
+
+use crate::logging::with_request_id;
+
+pub const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+const MAX_LENGTH: usize = 128;
+
+// Keeps the X-Request-Id sent by the client or a proxy in front of the service, or generates one,
+// so the request's log lines and its calls to the nutrition API can be matched up with it. The ID
+// is returned in the response, and each request is logged once it has been answered
+pub async fn assign(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, Error> {
+    let id = req.headers()
+        .get(&REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid(id))
+        .map(String::from)
+        .unwrap_or_else(generate);
+
+    let started = Instant::now();
+    let peer = req.connection_info().realip_remote_addr().unwrap_or("-").to_string();
+    let method = req.method().to_string();
+    let uri = req.uri().to_string();
+
+    with_request_id(id.clone(), async move {
+        // Handlers and extractors have already turned their errors into responses, so only errors
+        // of the other middleware are left without the header
+        let res = next.call(req).await.map(|mut res| {
+            if let Ok(value) = HeaderValue::from_str(&id) {
+                res.headers_mut().insert(REQUEST_ID, value);
+            }
+
+            res.map_into_boxed_body()
+        });
+
+        let status = match &res {
+            Ok(res) => res.status(),
+            Err(err) => err.as_response_error().status_code()
+        };
+
+        log::info!("{} \"{} {}\" {} {:.6}", peer, method, uri, status.as_u16(), started.elapsed().as_secs_f64());
+
+        res
+    }).await
+}
+
+// IDs end up in log lines and outgoing headers, so only short ones made of a few safe characters
+// are kept
+fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_LENGTH
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' || c == ':')
+}
+
+// Random (version 4) UUID
+fn generate() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = hex::encode(bytes);
+
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
@@ -0,0 +1,58 @@
+use serde::Deserialize;
+
+use utoipa::{
+    IntoParams,
+    ToSchema,
+    PartialSchema,
+    openapi::{RefOr, Schema}
+};
+
+use crate::repository::{
+    state::{Dish, Meal},
+    search::SearchResult
+};
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQuery {
+    /// Text to search for
+    q: Option<String>,
+    /// Maximum number of results, 20 by default and at most 100
+    limit: Option<usize>
+}
+
+impl SearchQuery {
+    pub fn get_query(&self) -> Option<&String> {
+        self.q.as_ref().filter(|q| !q.trim().is_empty())
+    }
+
+    pub fn get_limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT)
+    }
+}
+
+
+// The path macro can't take generic response bodies, so these stand in for
+// SearchResult<Dish> and SearchResult<Meal> in the OpenAPI document
+pub struct DishSearchResult;
+
+impl PartialSchema for DishSearchResult {
+    fn schema() -> RefOr<Schema> {
+        SearchResult::<Dish>::schema()
+    }
+}
+
+impl ToSchema for DishSearchResult {}
+
+pub struct MealSearchResult;
+
+impl PartialSchema for MealSearchResult {
+    fn schema() -> RefOr<Schema> {
+        SearchResult::<Meal>::schema()
+    }
+}
+
+impl ToSchema for MealSearchResult {}
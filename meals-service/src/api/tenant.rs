@@ -0,0 +1,113 @@
+use actix_web::{
+    get,
+    put,
+    web::{Path, Data},
+    HttpResponse,
+    http::header::ContentType,
+};
+
+use crate::{
+    repository::{
+        state::AppState,
+        tenants::{is_valid_name, Quota, TenantSummary}
+    },
+    api::{
+        representation::{Format, reply},
+        body::Body,
+        auth::{Authorized, Admin}
+    }
+};
+
+#[utoipa::path(
+    tag = "tenants",
+    responses(
+        (status = 200, description = "Every tenant, by name, with its number of dishes and meals and its quota", body = Vec<TenantSummary>),
+        (status = 401, description = "A valid API key or token is required (-12)", body = i32),
+        (status = 403, description = "The API key or token does not have the admin role (-13)", body = i32)
+    )
+)]
+#[get("/tenants")]
+pub async fn get_tenants(_auth: Authorized<Admin>, format: Format, state: Data<AppState>) -> HttpResponse {
+    reply(HttpResponse::Ok(), format, &state.get_tenants())
+}
+
+#[utoipa::path(
+    tag = "tenants",
+    params(("name" = String, Path, description = "Name of the tenant")),
+    responses(
+        (status = 200, description = "Number of dishes and meals of the tenant and its quota", body = TenantSummary),
+        (status = 401, description = "A valid API key or token is required (-12)", body = i32),
+        (status = 403, description = "The API key or token does not have the admin role (-13)", body = i32),
+        (status = 404, description = "Tenant not found (-5)", body = i32)
+    )
+)]
+#[get("/tenants/{name}")]
+pub async fn get_tenant(_auth: Authorized<Admin>, path: Path<String>, format: Format, state: Data<AppState>) -> HttpResponse {
+    match state.get_tenant(&path.into_inner()) {
+        Ok(tenant) => reply(HttpResponse::Ok(), format, &tenant.summary()),
+        Err(err_id) => {
+            HttpResponse::NotFound()
+                .content_type(ContentType::json())
+                .json(err_id)
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "tenants",
+    params(("name" = String, Path, description = "Name of the tenant")),
+    responses(
+        (status = 201, description = "The new tenant, with no dishes or meals and the default quota", body = TenantSummary),
+        (status = 200, description = "The tenant, which already existed and is left as it was", body = TenantSummary),
+        (status = 401, description = "A valid API key or token is required (-12)", body = i32),
+        (status = 403, description = "The API key or token does not have the admin role (-13)", body = i32),
+        (status = 422, description = "Invalid tenant name (-15)", body = i32)
+    )
+)]
+#[put("/tenants/{name}")]
+pub async fn create_tenant(_auth: Authorized<Admin>, path: Path<String>, format: Format, state: Data<AppState>) -> HttpResponse {
+    let name = path.into_inner();
+
+    if !is_valid_name(&name) {
+        return HttpResponse::UnprocessableEntity()
+            .content_type(ContentType::json())
+            .json(-15)
+    }
+
+    match state.create_tenant(&name) {
+        (tenant, true) => reply(HttpResponse::Created(), format, &tenant.summary()),
+        (tenant, false) => reply(HttpResponse::Ok(), format, &tenant.summary())
+    }
+}
+
+#[utoipa::path(
+    tag = "tenants",
+    params(("name" = String, Path, description = "Name of the tenant")),
+    request_body = Quota,
+    responses(
+        (status = 200, description = "The tenant with its new quota", body = TenantSummary),
+        (status = 401, description = "A valid API key or token is required (-12)", body = i32),
+        (status = 403, description = "The API key or token does not have the admin role (-13)", body = i32),
+        (status = 404, description = "Tenant not found (-5)", body = i32),
+        (status = 413, description = "Request body is too large (-8)", body = i32),
+        (status = 415, description = "Content-Type is not JSON, YAML or MessagePack (0)", body = i32),
+        (status = 422, description = "Invalid body (-1)", body = i32)
+    )
+)]
+#[put("/tenants/{name}/quota")]
+pub async fn update_quota(_auth: Authorized<Admin>, path: Path<String>, data: Body<Quota>, state: Data<AppState>) -> HttpResponse {
+    let tenant = match state.get_tenant(&path.into_inner()) {
+        Ok(tenant) => tenant,
+        Err(err_id) => {
+            return HttpResponse::NotFound()
+                .content_type(ContentType::json())
+                .json(err_id)
+        }
+    };
+
+    tenant.set_quota(data.into_inner());
+
+    HttpResponse::Ok()
+        .content_type(ContentType::json())
+        .json(tenant.summary())
+}
@@ -0,0 +1,49 @@
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    Error
+};
+
+use crate::telemetry::{Span, SpanContext, Kind};
+
+// Runs each request in a server span named after its route, e.g. "POST /dishes", continuing the
+// trace of a W3C traceparent header sent by the caller. Spans started by the handler, such as those
+// of AppState operations and nutrition API calls, are its children
+pub async fn trace(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, Error> {
+    let parent = req.headers()
+        .get("traceparent")
+        .and_then(|value| value.to_str().ok())
+        .and_then(SpanContext::from_traceparent);
+
+    let route = req.match_pattern();
+
+    let name = match &route {
+        Some(route) => format!("{} {}", req.method(), route),
+        None => req.method().to_string()
+    };
+
+    let mut span = Span::child_of(name, Kind::Server, parent);
+
+    span.set_attribute("http.request.method", req.method().as_str());
+    span.set_attribute("url.path", req.path());
+
+    if let Some(route) = route {
+        span.set_attribute("http.route", route);
+    }
+
+    let res = span.scope(next.call(req)).await;
+
+    let status = match &res {
+        Ok(res) => res.status(),
+        Err(err) => err.as_response_error().status_code()
+    };
+
+    span.set_attribute("http.response.status_code", status.as_u16());
+
+    if status.is_server_error() {
+        span.set_error(status.to_string());
+    }
+
+    res.map(ServiceResponse::map_into_boxed_body)
+}
@@ -0,0 +1,189 @@
+use actix_web::{
+    get,
+    post,
+    put,
+    delete,
+    web::Path,
+    HttpRequest,
+    HttpResponse,
+    http::header::ContentType
+};
+
+use crate::{
+    repository::state::Dish,
+    api::{
+        dish::CreateDishRequest,
+        list::{ListParams, parse_list_query, list_response},
+        path::decode_name,
+        representation::{Format, reply},
+        body::{Body, BodyError},
+        conditional::{tagged, not_modified, if_match},
+        v2::{ApiError, DishPage},
+        auth::{Authorized, Reader, Editor, CurrentTenant}
+    }
+};
+
+#[utoipa::path(
+    context_path = "/v2",
+    tag = "v2",
+    params(ListParams),
+    responses(
+        (status = 200, description = "Page of dishes in the requested order", body = DishPage, headers(("ETag" = String, description = "Version of the dish collection"))),
+        (status = 304, description = "The dishes haven't changed since the version given in If-None-Match"),
+        (status = 422, description = "Invalid query parameter", body = ApiError)
+    )
+)]
+#[get("/dishes")]
+pub async fn get_dishes(_auth: Authorized<Reader>, req: HttpRequest, format: Format, state: CurrentTenant) -> Result<HttpResponse, ApiError> {
+    let options = parse_list_query(req.query_string()).map_err(ApiError::new)?;
+
+    let version = state.get_dishes_version();
+
+    if let Some(res) = not_modified(&req, version, format) {
+        return Ok(res)
+    }
+
+    let (total, dishes) = state.get_dishes(&options);
+
+    // CSV has no room for the paging metadata, so it gets the same rows and X-Total-Count header as the legacy route
+    if format == Format::Csv {
+        return Ok(list_response(tagged(HttpResponse::Ok(), version, format), format, total, &dishes))
+    }
+
+    Ok(reply(tagged(HttpResponse::Ok(), version, format), format, &DishPage::new(dishes, total, &options)))
+}
+
+#[utoipa::path(
+    context_path = "/v2",
+    tag = "v2",
+    request_body = CreateDishRequest,
+    responses(
+        (status = 201, description = "The new dish", body = Dish),
+        (status = 409, description = "A dish with the given name already exists", body = ApiError),
+        (status = 413, description = "Request body is too large", body = ApiError),
+        (status = 415, description = "Content-Type is not JSON, YAML or MessagePack", body = ApiError),
+        (status = 422, description = "Invalid body, unrecognized name or multi-item match not accepted", body = ApiError),
+        (status = 429, description = "The tenant has reached its dish quota", body = ApiError),
+        (status = 502, description = "Nutrition API not reachable", body = ApiError)
+    )
+)]
+#[post("/dishes")]
+pub async fn create_dish(_auth: Authorized<Editor>, data: Result<Body<CreateDishRequest>, BodyError>, state: CurrentTenant) -> Result<HttpResponse, ApiError> {
+    let data = data?;
+
+    let id = state.create_dish(data.name.clone(), &data.multi_item, &data.items).await.map_err(ApiError::new)?;
+
+    let dish = state.get_dish_by_id(id).map_err(ApiError::new)?;
+
+    Ok(
+        HttpResponse::Created()
+            .content_type(ContentType::json())
+            .insert_header(("Location", format!("/v2/dishes/{}", id)))
+            .json(dish)
+    )
+}
+
+#[utoipa::path(
+    context_path = "/v2",
+    tag = "v2",
+    params(("id" = i32, Path, description = "ID of the dish")),
+    responses(
+        (status = 200, description = "The dish", body = Dish, headers(("ETag" = String, description = "Version of the dish"))),
+        (status = 304, description = "The dish hasn't changed since the version given in If-None-Match"),
+        (status = 404, description = "Dish not found", body = ApiError)
+    )
+)]
+#[get("/dishes/{id}")]
+pub async fn get_dish(_auth: Authorized<Reader>, req: HttpRequest, path: Path<i32>, format: Format, state: CurrentTenant) -> Result<HttpResponse, ApiError> {
+    let dish = state.get_dish_by_id(path.into_inner()).map_err(ApiError::new)?;
+
+    if let Some(res) = not_modified(&req, dish.version(), format) {
+        return Ok(res)
+    }
+
+    Ok(reply(tagged(HttpResponse::Ok(), dish.version(), format), format, &dish))
+}
+
+#[utoipa::path(
+    context_path = "/v2",
+    tag = "v2",
+    params(("name" = String, Path, description = "Name of the dish, percent-encoded")),
+    responses(
+        (status = 200, description = "The dish", body = Dish, headers(("ETag" = String, description = "Version of the dish"))),
+        (status = 304, description = "The dish hasn't changed since the version given in If-None-Match"),
+        (status = 404, description = "Dish not found", body = ApiError)
+    )
+)]
+#[get("/dishes/name/{name:.*}")]
+pub async fn get_dish_with_name(_auth: Authorized<Reader>, req: HttpRequest, path: Path<String>, format: Format, state: CurrentTenant) -> Result<HttpResponse, ApiError> {
+    let dish = state.get_dish_by_name(decode_name(path.into_inner())).map_err(ApiError::new)?;
+
+    if let Some(res) = not_modified(&req, dish.version(), format) {
+        return Ok(res)
+    }
+
+    Ok(reply(tagged(HttpResponse::Ok(), dish.version(), format), format, &dish))
+}
+
+#[utoipa::path(
+    context_path = "/v2",
+    tag = "v2",
+    params(("id" = i32, Path, description = "ID of the dish")),
+    responses(
+        (status = 204, description = "Dish deleted"),
+        (status = 404, description = "Dish not found", body = ApiError)
+    )
+)]
+#[delete("/dishes/{id}")]
+pub async fn delete_dish(_auth: Authorized<Editor>, path: Path<i32>, state: CurrentTenant) -> Result<HttpResponse, ApiError> {
+    state.delete_dish_by_id(path.into_inner()).map_err(ApiError::new)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    context_path = "/v2",
+    tag = "v2",
+    params(("name" = String, Path, description = "Name of the dish, percent-encoded")),
+    responses(
+        (status = 204, description = "Dish deleted"),
+        (status = 404, description = "Dish not found", body = ApiError)
+    )
+)]
+#[delete("/dishes/name/{name:.*}")]
+pub async fn delete_dish_with_name(_auth: Authorized<Editor>, path: Path<String>, state: CurrentTenant) -> Result<HttpResponse, ApiError> {
+    state.delete_dish_by_name(decode_name(path.into_inner())).map_err(ApiError::new)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    context_path = "/v2",
+    tag = "v2",
+    params(("id" = i32, Path, description = "ID of the dish")),
+    request_body = CreateDishRequest,
+    responses(
+        (status = 200, description = "The updated dish", body = Dish, headers(("ETag" = String, description = "New version of the dish"))),
+        (status = 404, description = "Dish not found", body = ApiError),
+        (status = 409, description = "Another dish already has the name", body = ApiError),
+        (status = 412, description = "The dish changed since the version given in If-Match", body = ApiError),
+        (status = 413, description = "Request body is too large", body = ApiError),
+        (status = 415, description = "Content-Type is not JSON, YAML or MessagePack", body = ApiError),
+        (status = 422, description = "Invalid body, unrecognized name or multi-item match not accepted", body = ApiError),
+        (status = 502, description = "Nutrition API not reachable", body = ApiError)
+    )
+)]
+#[put("/dishes/{id}")]
+pub async fn update_dish(_auth: Authorized<Editor>, req: HttpRequest, path: Path<i32>, data: Result<Body<CreateDishRequest>, BodyError>, state: CurrentTenant) -> Result<HttpResponse, ApiError> {
+    let data = data?.into_inner();
+
+    let id = state.update_dish(path.into_inner(), data.name, &data.multi_item, &data.items, if_match(&req)).await.map_err(ApiError::new)?;
+
+    let dish = state.get_dish_by_id(id).map_err(ApiError::new)?;
+
+    Ok(
+        tagged(HttpResponse::Ok(), dish.version(), Format::Json)
+            .content_type(ContentType::json())
+            .json(dish)
+    )
+}
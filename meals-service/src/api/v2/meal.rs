@@ -0,0 +1,187 @@
+use actix_web::{
+    get,
+    post,
+    put,
+    delete,
+    web::Path,
+    HttpRequest,
+    HttpResponse,
+    http::header::ContentType
+};
+
+use crate::{
+    repository::state::Meal,
+    api::{
+        meal::CreateMealRequest,
+        list::{ListParams, parse_list_query, list_response},
+        path::decode_name,
+        representation::{Format, reply},
+        body::{Body, BodyError},
+        conditional::{tagged, not_modified, if_match},
+        v2::{ApiError, MealPage},
+        auth::{Authorized, Reader, Editor, CurrentTenant}
+    }
+};
+
+#[utoipa::path(
+    context_path = "/v2",
+    tag = "v2",
+    params(ListParams),
+    responses(
+        (status = 200, description = "Page of meals in the requested order", body = MealPage, headers(("ETag" = String, description = "Version of the meal collection"))),
+        (status = 304, description = "The meals haven't changed since the version given in If-None-Match"),
+        (status = 422, description = "Invalid query parameter", body = ApiError)
+    )
+)]
+#[get("/meals")]
+pub async fn get_meals(_auth: Authorized<Reader>, req: HttpRequest, format: Format, state: CurrentTenant) -> Result<HttpResponse, ApiError> {
+    let options = parse_list_query(req.query_string()).map_err(ApiError::new)?;
+
+    let version = state.get_meals_version();
+
+    if let Some(res) = not_modified(&req, version, format) {
+        return Ok(res)
+    }
+
+    let (total, meals) = state.get_meals(&options);
+
+    // CSV has no room for the paging metadata, so it gets the same rows and X-Total-Count header as the legacy route
+    if format == Format::Csv {
+        return Ok(list_response(tagged(HttpResponse::Ok(), version, format), format, total, &meals))
+    }
+
+    Ok(reply(tagged(HttpResponse::Ok(), version, format), format, &MealPage::new(meals, total, &options)))
+}
+
+#[utoipa::path(
+    context_path = "/v2",
+    tag = "v2",
+    request_body = CreateMealRequest,
+    responses(
+        (status = 201, description = "The new meal", body = Meal),
+        (status = 409, description = "A meal with the given name already exists", body = ApiError),
+        (status = 413, description = "Request body is too large", body = ApiError),
+        (status = 415, description = "Content-Type is not JSON, YAML or MessagePack", body = ApiError),
+        (status = 422, description = "Invalid body or unknown dish ID", body = ApiError),
+        (status = 429, description = "The tenant has reached its meal quota", body = ApiError)
+    )
+)]
+#[post("/meals")]
+pub async fn create_meal(_auth: Authorized<Editor>, data: Result<Body<CreateMealRequest>, BodyError>, state: CurrentTenant) -> Result<HttpResponse, ApiError> {
+    let data = data?.into_inner();
+
+    let id = state.create_meal(data.name, &data.appetizer, &data.main, &data.dessert).map_err(ApiError::new)?;
+
+    let meal = state.get_meal_by_id(id).map_err(ApiError::new)?;
+
+    Ok(
+        HttpResponse::Created()
+            .content_type(ContentType::json())
+            .insert_header(("Location", format!("/v2/meals/{}", id)))
+            .json(meal)
+    )
+}
+
+#[utoipa::path(
+    context_path = "/v2",
+    tag = "v2",
+    params(("id" = i32, Path, description = "ID of the meal")),
+    responses(
+        (status = 200, description = "The meal", body = Meal, headers(("ETag" = String, description = "Version of the meal"))),
+        (status = 304, description = "The meal hasn't changed since the version given in If-None-Match"),
+        (status = 404, description = "Meal not found", body = ApiError)
+    )
+)]
+#[get("/meals/{id}")]
+pub async fn get_meal(_auth: Authorized<Reader>, req: HttpRequest, path: Path<i32>, format: Format, state: CurrentTenant) -> Result<HttpResponse, ApiError> {
+    let meal = state.get_meal_by_id(path.into_inner()).map_err(ApiError::new)?;
+
+    if let Some(res) = not_modified(&req, meal.version(), format) {
+        return Ok(res)
+    }
+
+    Ok(reply(tagged(HttpResponse::Ok(), meal.version(), format), format, &meal))
+}
+
+#[utoipa::path(
+    context_path = "/v2",
+    tag = "v2",
+    params(("name" = String, Path, description = "Name of the meal, percent-encoded")),
+    responses(
+        (status = 200, description = "The meal", body = Meal, headers(("ETag" = String, description = "Version of the meal"))),
+        (status = 304, description = "The meal hasn't changed since the version given in If-None-Match"),
+        (status = 404, description = "Meal not found", body = ApiError)
+    )
+)]
+#[get("/meals/name/{name:.*}")]
+pub async fn get_meal_with_name(_auth: Authorized<Reader>, req: HttpRequest, path: Path<String>, format: Format, state: CurrentTenant) -> Result<HttpResponse, ApiError> {
+    let meal = state.get_meal_by_name(&decode_name(path.into_inner())).map_err(ApiError::new)?;
+
+    if let Some(res) = not_modified(&req, meal.version(), format) {
+        return Ok(res)
+    }
+
+    Ok(reply(tagged(HttpResponse::Ok(), meal.version(), format), format, &meal))
+}
+
+#[utoipa::path(
+    context_path = "/v2",
+    tag = "v2",
+    params(("id" = i32, Path, description = "ID of the meal")),
+    responses(
+        (status = 204, description = "Meal deleted"),
+        (status = 404, description = "Meal not found", body = ApiError)
+    )
+)]
+#[delete("/meals/{id}")]
+pub async fn delete_meal(_auth: Authorized<Editor>, path: Path<i32>, state: CurrentTenant) -> Result<HttpResponse, ApiError> {
+    state.delete_meal_by_id(&path.into_inner()).map_err(ApiError::new)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    context_path = "/v2",
+    tag = "v2",
+    params(("name" = String, Path, description = "Name of the meal, percent-encoded")),
+    responses(
+        (status = 204, description = "Meal deleted"),
+        (status = 404, description = "Meal not found", body = ApiError)
+    )
+)]
+#[delete("/meals/name/{name:.*}")]
+pub async fn delete_meal_with_name(_auth: Authorized<Editor>, path: Path<String>, state: CurrentTenant) -> Result<HttpResponse, ApiError> {
+    state.delete_meal_by_name(&decode_name(path.into_inner())).map_err(ApiError::new)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    context_path = "/v2",
+    tag = "v2",
+    params(("id" = i32, Path, description = "ID of the meal")),
+    request_body = CreateMealRequest,
+    responses(
+        (status = 200, description = "The updated meal", body = Meal, headers(("ETag" = String, description = "New version of the meal"))),
+        (status = 404, description = "Meal not found", body = ApiError),
+        (status = 409, description = "Another meal already has the name", body = ApiError),
+        (status = 412, description = "The meal changed since the version given in If-Match", body = ApiError),
+        (status = 413, description = "Request body is too large", body = ApiError),
+        (status = 415, description = "Content-Type is not JSON, YAML or MessagePack", body = ApiError),
+        (status = 422, description = "Invalid body or unknown dish ID", body = ApiError)
+    )
+)]
+#[put("/meals/{id}")]
+pub async fn update_meal(_auth: Authorized<Editor>, req: HttpRequest, path: Path<i32>, data: Result<Body<CreateMealRequest>, BodyError>, state: CurrentTenant) -> Result<HttpResponse, ApiError> {
+    let data = data?;
+
+    let id = state.update_meal(&path.into_inner(), &data.name, &data.appetizer, &data.main, &data.dessert, if_match(&req)).map_err(ApiError::new)?;
+
+    let meal = state.get_meal_by_id(id).map_err(ApiError::new)?;
+
+    Ok(
+        tagged(HttpResponse::Ok(), meal.version(), Format::Json)
+            .content_type(ContentType::json())
+            .json(meal)
+    )
+}
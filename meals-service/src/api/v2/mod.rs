@@ -0,0 +1,180 @@
+pub mod dish;
+pub mod meal;
+
+use std::fmt;
+
+use actix_web::{
+    web::{self, ServiceConfig, PathConfig, QueryConfig},
+    http::{StatusCode, header::ContentType},
+    HttpResponse,
+    ResponseError
+};
+
+use serde::Serialize;
+
+use utoipa::ToSchema;
+
+use crate::{
+    repository::{
+        state::{Dish, Meal},
+        listing::ListOptions
+    },
+    api::{body::BodyError, Operation, route}
+};
+
+// JSON error document returned by every /v2 route, carrying the same codes as the legacy routes
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiError {
+    code: i32,
+    message: String
+}
+
+impl ApiError {
+    pub fn new(code: i32) -> ApiError {
+        let message = match code {
+            0 => "Content-Type must be JSON, YAML or MessagePack",
+            -1 => "A required parameter is missing or invalid",
+            -2 => "A resource with the given name already exists",
+            -3 => "The nutrition API does not recognize the name of the dish",
+            -4 => "The nutrition API is not reachable",
+            -5 => "Resource not found",
+            -6 => "At least one of the dish IDs given does not correspond to a dish",
+            -7 => "The nutrition API matched several items and multi_item did not accept them",
+            -8 => "The request body is too large",
+            -9 => "The resource was modified since the version given in If-Match",
+            -10 => "The Idempotency-Key was already used for a different request",
+            -11 => "A request with the same Idempotency-Key is still being processed",
+            -12 => "A valid API key or token is required",
+            -13 => "The role of the API key or token does not allow this operation",
+            -14 => "The tenant has reached its quota for this kind of resource",
+            -15 => "The tenant name is not valid",
+            -16 => "API keys can't be created while authentication is disabled",
+            -17 => "The last admin API key can't be revoked",
+            _ => "Unexpected error"
+        };
+
+        ApiError {
+            code,
+            message: String::from(message)
+        }
+    }
+
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self.code {
+            0 => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            -2 | -11 | -16 | -17 => StatusCode::CONFLICT,
+            -4 => StatusCode::BAD_GATEWAY,
+            -5 => StatusCode::NOT_FOUND,
+            -8 => StatusCode::PAYLOAD_TOO_LARGE,
+            -9 => StatusCode::PRECONDITION_FAILED,
+            -12 => StatusCode::UNAUTHORIZED,
+            -13 => StatusCode::FORBIDDEN,
+            -14 => StatusCode::TOO_MANY_REQUESTS,
+            -1 | -3 | -6 | -7 | -10 | -15 => StatusCode::UNPROCESSABLE_ENTITY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code())
+            .content_type(ContentType::json())
+            .json(self)
+    }
+}
+
+// Error in the style of the route a request was sent to, for errors raised before its handler runs:
+// a JSON document under /v2 and a bare code elsewhere
+pub fn error_for(path: &str, code: i32) -> HttpResponse {
+    match path.starts_with("/v2/") {
+        true => ApiError::new(code).error_response(),
+        false => {
+            HttpResponse::build(ApiError::new(code).status_code())
+                .content_type(ContentType::json())
+                .json(code)
+        }
+    }
+}
+
+impl From<BodyError> for ApiError {
+    fn from(err: BodyError) -> ApiError {
+        ApiError::new(err.code())
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DishPage {
+    items: Vec<Dish>,
+    total: usize,
+    offset: usize,
+    limit: Option<usize>
+}
+
+impl DishPage {
+    pub fn new(items: Vec<Dish>, total: usize, options: &ListOptions) -> DishPage {
+        DishPage {
+            items,
+            total,
+            offset: options.offset,
+            limit: options.limit
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MealPage {
+    items: Vec<Meal>,
+    total: usize,
+    offset: usize,
+    limit: Option<usize>
+}
+
+impl MealPage {
+    pub fn new(items: Vec<Meal>, total: usize, options: &ListOptions) -> MealPage {
+        MealPage {
+            items,
+            total,
+            offset: options.offset,
+            limit: options.limit
+        }
+    }
+}
+
+pub fn configure(cfg: &mut ServiceConfig, operations: &mut Vec<Operation>) {
+    cfg.service(
+        web::scope("/v2")
+            .app_data(PathConfig::default().error_handler(|_, _| ApiError::new(-5).into()))
+            .app_data(QueryConfig::default().error_handler(|_, _| ApiError::new(-1).into()))
+            .configure(|cfg| {
+                route(cfg, operations, dish::get_dishes);
+                route(cfg, operations, dish::create_dish);
+                route(cfg, operations, dish::get_dish);
+                route(cfg, operations, dish::get_dish_with_name);
+                route(cfg, operations, dish::delete_dish);
+                route(cfg, operations, dish::delete_dish_with_name);
+                route(cfg, operations, dish::update_dish);
+                route(cfg, operations, meal::get_meals);
+                route(cfg, operations, meal::create_meal);
+                route(cfg, operations, meal::get_meal);
+                route(cfg, operations, meal::get_meal_with_name);
+                route(cfg, operations, meal::delete_meal);
+                route(cfg, operations, meal::delete_meal_with_name);
+                route(cfg, operations, meal::update_meal);
+            })
+    );
+}
@@ -0,0 +1,162 @@
+use actix_web::{
+    get,
+    post,
+    delete,
+    web::{Path, Data},
+    HttpResponse,
+    Error,
+    http::header::{ContentType, CacheControl, CacheDirective},
+};
+
+use serde::Deserialize;
+
+use utoipa::ToSchema;
+
+use crate::{
+    repository::{
+        state::AppState,
+        webhooks::{Subscription, Delivery},
+        webhook_client::ping
+    },
+    api::{
+        representation::{Format, reply},
+        body::Body,
+        auth::{Authorized, Admin, CurrentTenant}
+    }
+};
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    #[serde(default)]
+    pub events: Vec<String>,
+    pub secret: Option<String>
+}
+
+#[utoipa::path(
+    tag = "webhooks",
+    request_body = CreateWebhookRequest,
+    responses(
+        (status = 201, description = "The new subscription, including its secret", body = Subscription),
+        (status = 413, description = "Request body is too large (-8)", body = i32),
+        (status = 415, description = "Content-Type is not JSON, YAML or MessagePack (0)", body = i32),
+        (status = 422, description = "Missing or invalid URL, unknown event or empty secret (-1)", body = i32)
+    )
+)]
+#[post("/webhooks")]
+pub async fn create_webhook(_auth: Authorized<Admin>, data: Body<CreateWebhookRequest>, state: Data<AppState>, tenant: CurrentTenant) -> Result<HttpResponse, Error> {
+    let data = data.into_inner();
+
+    match state.webhooks().subscribe(tenant.name(), data.url, data.events, data.secret) {
+        Ok(subscription) => {
+            Ok(
+                HttpResponse::Created()
+                    .content_type(ContentType::json())
+                    .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+                    .json(subscription)
+            )
+        },
+        Err(err_id) => {
+            Ok(
+                HttpResponse::UnprocessableEntity()
+                    .content_type(ContentType::json())
+                    .json(err_id)
+            )
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "Every subscription, without secrets", body = Vec<Subscription>)
+    )
+)]
+#[get("/webhooks")]
+pub async fn get_webhooks(_auth: Authorized<Admin>, format: Format, state: Data<AppState>) -> HttpResponse {
+    reply(HttpResponse::Ok(), format, &state.webhooks().get_subscriptions())
+}
+
+#[utoipa::path(
+    tag = "webhooks",
+    params(("ID" = i32, Path, description = "ID of the subscription")),
+    responses(
+        (status = 200, description = "The subscription, without its secret", body = Subscription),
+        (status = 404, description = "Subscription not found (-5)", body = i32)
+    )
+)]
+#[get("/webhooks/{ID}")]
+pub async fn get_webhook(_auth: Authorized<Admin>, path: Path<i32>, format: Format, state: Data<AppState>) -> HttpResponse {
+    match state.webhooks().get_subscription(path.into_inner()) {
+        Ok(subscription) => reply(HttpResponse::Ok(), format, &subscription),
+        Err(err_id) => not_found(err_id)
+    }
+}
+
+#[utoipa::path(
+    tag = "webhooks",
+    params(("ID" = i32, Path, description = "ID of the subscription")),
+    responses(
+        (status = 200, description = "ID of the deleted subscription", body = i32),
+        (status = 404, description = "Subscription not found (-5)", body = i32)
+    )
+)]
+#[delete("/webhooks/{ID}")]
+pub async fn delete_webhook(_auth: Authorized<Admin>, path: Path<i32>, state: Data<AppState>) -> HttpResponse {
+    match state.webhooks().unsubscribe(path.into_inner()) {
+        Ok(id) => {
+            HttpResponse::Ok()
+                .content_type(ContentType::json())
+                .json(id)
+        },
+        Err(err_id) => not_found(err_id)
+    }
+}
+
+#[utoipa::path(
+    tag = "webhooks",
+    params(("ID" = i32, Path, description = "ID of the subscription")),
+    responses(
+        (status = 200, description = "Delivery attempts for the subscription, most recent first", body = Vec<Delivery>),
+        (status = 404, description = "Subscription not found (-5)", body = i32)
+    )
+)]
+#[get("/webhooks/{ID}/deliveries")]
+pub async fn get_webhook_deliveries(_auth: Authorized<Admin>, path: Path<i32>, format: Format, state: Data<AppState>) -> HttpResponse {
+    let id = path.into_inner();
+
+    match state.webhooks().is_subscribed(id) {
+        true => reply(HttpResponse::Ok(), format, &state.webhooks().get_deliveries(id)),
+        false => not_found(-5)
+    }
+}
+
+#[utoipa::path(
+    tag = "webhooks",
+    params(("ID" = i32, Path, description = "ID of the subscription")),
+    responses(
+        (status = 202, description = "A ping event is being sent to the subscription; the outcome appears in its deliveries", body = i32),
+        (status = 404, description = "Subscription not found (-5)", body = i32)
+    )
+)]
+#[post("/webhooks/{ID}/ping")]
+pub async fn ping_webhook(_auth: Authorized<Admin>, path: Path<i32>, state: Data<AppState>) -> HttpResponse {
+    match state.webhooks().get_recipient(path.into_inner()) {
+        Ok(subscription) => {
+            let id = subscription.id();
+
+            ping(state.into_inner(), subscription);
+
+            HttpResponse::Accepted()
+                .content_type(ContentType::json())
+                .json(id)
+        },
+        Err(err_id) => not_found(err_id)
+    }
+}
+
+fn not_found(err_id: i32) -> HttpResponse {
+    HttpResponse::NotFound()
+        .content_type(ContentType::json())
+        .json(err_id)
+}
@@ -0,0 +1,41 @@
+pub mod service;
+
+use std::{
+    future::Future,
+    io,
+    net::SocketAddr,
+    sync::Arc
+};
+
+use tonic::transport::Server;
+
+use crate::repository::state::AppState;
+
+use service::MealsService;
+
+// Code generated from proto/meals.proto by build.rs
+pub mod proto {
+    tonic::include_proto!("meals.v1");
+}
+
+use proto::meals_server::MealsServer;
+
+const DEFAULT_PORT: u16 = 50051;
+
+// Port for gRPC, separate from the HTTP one. GRPC_PORT overrides it
+pub fn port() -> u16 {
+    std::env::var("GRPC_PORT")
+        .ok()
+        .and_then(|port| port.parse::<u16>().ok())
+        .unwrap_or(DEFAULT_PORT)
+}
+
+// Serves the Meals service on the same AppState as the HTTP routes until shutdown resolves, then
+// stops accepting connections and returns once the calls in progress have finished
+pub async fn serve(state: Arc<AppState>, port: u16, shutdown: impl Future<Output = ()>) -> io::Result<()> {
+    Server::builder()
+        .add_service(MealsServer::new(MealsService::new(state)))
+        .serve_with_shutdown(SocketAddr::from(([0, 0, 0, 0], port)), shutdown)
+        .await
+        .map_err(io::Error::other)
+}
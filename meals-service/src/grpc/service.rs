@@ -0,0 +1,328 @@
+use std::sync::Arc;
+
+use tonic::{
+    metadata::{MetadataMap, MetadataValue},
+    Code,
+    Request,
+    Response,
+    Status
+};
+
+use crate::{
+    repository::{
+        state::{self, AppState, Tenant},
+        listing::{Field, Comparison, Direction, Filter, Listable, ListOptions},
+        nutrition_api_client::MultiItemPolicy,
+        api_keys::Role
+    },
+    api::{v2::ApiError, auth::{is_enabled, grant_for, tenant_for}}
+};
+
+use super::proto::{self, lookup::Key, meals_server::Meals};
+
+pub struct MealsService {
+    state: Arc<AppState>
+}
+
+impl MealsService {
+    pub fn new(state: Arc<AppState>) -> MealsService {
+        MealsService { state }
+    }
+}
+
+#[tonic::async_trait]
+impl Meals for MealsService {
+    async fn list_dishes(&self, request: Request<proto::ListRequest>) -> Result<Response<proto::ListDishesResponse>, Status> {
+        let tenant = self.authorize(&request, Role::Reader)?;
+
+        let options = list_options(request.into_inner()).map_err(status)?;
+
+        let (total, dishes) = tenant.get_dishes(&options);
+
+        Ok(Response::new(proto::ListDishesResponse {
+            dishes: dishes.iter().map(to_dish).collect(),
+            total: total as u32
+        }))
+    }
+
+    async fn get_dish(&self, request: Request<proto::Lookup>) -> Result<Response<proto::Dish>, Status> {
+        let tenant = self.authorize(&request, Role::Reader)?;
+
+        let dish = match request.into_inner().key {
+            Some(Key::Id(id)) => tenant.get_dish_by_id(id),
+            Some(Key::Name(name)) => tenant.get_dish_by_name(name),
+            None => Err(-1)
+        };
+
+        dish.map(|dish| Response::new(to_dish(&dish))).map_err(status)
+    }
+
+    async fn create_dish(&self, request: Request<proto::CreateDishRequest>) -> Result<Response<proto::Dish>, Status> {
+        let tenant = self.authorize(&request, Role::Editor)?;
+
+        let data = request.into_inner();
+        let policy = to_policy(data.multi_item).map_err(status)?;
+
+        let id = tenant.create_dish(data.name, &policy, &data.items).await.map_err(status)?;
+
+        dish(&tenant, id)
+    }
+
+    async fn update_dish(&self, request: Request<proto::UpdateDishRequest>) -> Result<Response<proto::Dish>, Status> {
+        let tenant = self.authorize(&request, Role::Editor)?;
+
+        let data = request.into_inner();
+        let policy = to_policy(data.multi_item).map_err(status)?;
+
+        let id = tenant.update_dish(data.id, data.name, &policy, &data.items, precondition(data.expected_version)).await.map_err(status)?;
+
+        dish(&tenant, id)
+    }
+
+    async fn delete_dish(&self, request: Request<proto::Lookup>) -> Result<Response<proto::DeleteResponse>, Status> {
+        let tenant = self.authorize(&request, Role::Editor)?;
+
+        let id = match request.into_inner().key {
+            Some(Key::Id(id)) => tenant.delete_dish_by_id(id),
+            Some(Key::Name(name)) => tenant.delete_dish_by_name(name),
+            None => Err(-1)
+        };
+
+        id.map(|id| Response::new(proto::DeleteResponse { id })).map_err(status)
+    }
+
+    async fn list_meals(&self, request: Request<proto::ListRequest>) -> Result<Response<proto::ListMealsResponse>, Status> {
+        let tenant = self.authorize(&request, Role::Reader)?;
+
+        let options = list_options(request.into_inner()).map_err(status)?;
+
+        let (total, meals) = tenant.get_meals(&options);
+
+        Ok(Response::new(proto::ListMealsResponse {
+            meals: meals.iter().map(to_meal).collect(),
+            total: total as u32
+        }))
+    }
+
+    async fn get_meal(&self, request: Request<proto::Lookup>) -> Result<Response<proto::Meal>, Status> {
+        let tenant = self.authorize(&request, Role::Reader)?;
+
+        let meal = match request.into_inner().key {
+            Some(Key::Id(id)) => tenant.get_meal_by_id(id),
+            Some(Key::Name(name)) => tenant.get_meal_by_name(&name),
+            None => Err(-1)
+        };
+
+        meal.map(|meal| Response::new(to_meal(&meal))).map_err(status)
+    }
+
+    async fn create_meal(&self, request: Request<proto::CreateMealRequest>) -> Result<Response<proto::Meal>, Status> {
+        let tenant = self.authorize(&request, Role::Editor)?;
+
+        let data = request.into_inner();
+
+        let id = tenant.create_meal(data.name, &data.appetizer, &data.main, &data.dessert).map_err(status)?;
+
+        meal(&tenant, id)
+    }
+
+    async fn update_meal(&self, request: Request<proto::UpdateMealRequest>) -> Result<Response<proto::Meal>, Status> {
+        let tenant = self.authorize(&request, Role::Editor)?;
+
+        let data = request.into_inner();
+
+        let id = tenant.update_meal(&data.id, &data.name, &data.appetizer, &data.main, &data.dessert, precondition(data.expected_version)).map_err(status)?;
+
+        meal(&tenant, id)
+    }
+
+    async fn delete_meal(&self, request: Request<proto::Lookup>) -> Result<Response<proto::DeleteResponse>, Status> {
+        let tenant = self.authorize(&request, Role::Editor)?;
+
+        let id = match request.into_inner().key {
+            Some(Key::Id(id)) => tenant.delete_meal_by_id(&id),
+            Some(Key::Name(name)) => tenant.delete_meal_by_name(&name),
+            None => Err(-1)
+        };
+
+        id.map(|id| Response::new(proto::DeleteResponse { id })).map_err(status)
+    }
+}
+
+impl MealsService {
+    // Same API keys, JWTs, roles and tenants as the HTTP routes, sent as "authorization: Bearer
+    // <key>" or "x-api-key: <key>" and "x-tenant-id: <tenant>" metadata. Returns the tenant the
+    // request works on
+    fn authorize<T>(&self, request: &Request<T>, minimum: Role) -> Result<Arc<Tenant>, Status> {
+        let metadata = request.metadata();
+
+        let grant = match is_enabled(&self.state) {
+            true => {
+                let key = metadata
+                    .get("authorization")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.strip_prefix("Bearer "))
+                    .or_else(|| metadata.get("x-api-key").and_then(|value| value.to_str().ok()));
+
+                match key.and_then(|key| grant_for(&self.state, key.trim())) {
+                    Some(grant) if grant.role >= minimum => Some(grant),
+                    Some(_) => return Err(status(-13)),
+                    None => return Err(status(-12))
+                }
+            },
+            false => None
+        };
+
+        let header = metadata.get("x-tenant-id").map(|value| value.to_str().unwrap_or_default().trim());
+
+        let name = tenant_for(header, grant.as_ref()).map_err(status)?;
+
+        self.state.get_tenant(&name).map_err(status)
+    }
+}
+
+fn dish(tenant: &Tenant, id: i32) -> Result<Response<proto::Dish>, Status> {
+    tenant.get_dish_by_id(id).map(|dish| Response::new(to_dish(&dish))).map_err(status)
+}
+
+fn meal(tenant: &Tenant, id: i32) -> Result<Response<proto::Meal>, Status> {
+    tenant.get_meal_by_id(id).map(|meal| Response::new(to_meal(&meal))).map_err(status)
+}
+
+fn to_dish(dish: &state::Dish) -> proto::Dish {
+    proto::Dish {
+        id: dish.id(),
+        name: dish.name().to_string(),
+        cal: dish.nutrient(Field::Cal).unwrap_or_default(),
+        size: dish.nutrient(Field::Size).unwrap_or_default(),
+        sodium: dish.nutrient(Field::Sodium).unwrap_or_default(),
+        sugar: dish.nutrient(Field::Sugar).unwrap_or_default(),
+        items: dish.items().iter().map(|item| proto::DishItem {
+            name: item.name().to_string(),
+            cal: item.nutrient(Field::Cal).unwrap_or_default(),
+            size: item.nutrient(Field::Size).unwrap_or_default(),
+            sodium: item.nutrient(Field::Sodium).unwrap_or_default(),
+            sugar: item.nutrient(Field::Sugar).unwrap_or_default()
+        }).collect(),
+        version: dish.version()
+    }
+}
+
+fn to_meal(meal: &state::Meal) -> proto::Meal {
+    let [appetizer, main, dessert] = meal.courses();
+
+    proto::Meal {
+        id: meal.id(),
+        name: meal.name().to_string(),
+        appetizer,
+        main,
+        dessert,
+        cal: meal.nutrient(Field::Cal).unwrap_or_default(),
+        sodium: meal.nutrient(Field::Sodium).unwrap_or_default(),
+        sugar: meal.nutrient(Field::Sugar).unwrap_or_default(),
+        version: meal.version()
+    }
+}
+
+// Enum values the server doesn't know are rejected with -1, like unknown query parameter values
+fn list_options(request: proto::ListRequest) -> Result<ListOptions, i32> {
+    let mut options = ListOptions::default();
+
+    for filter in request.filters {
+        let field = to_field(filter.field)?.ok_or(-1)?;
+
+        let comparison = match proto::Comparison::try_from(filter.comparison).map_err(|_| -1)? {
+            proto::Comparison::Lt => Comparison::Lt,
+            proto::Comparison::Lte => Comparison::Lte,
+            proto::Comparison::Gt => Comparison::Gt,
+            proto::Comparison::Gte => Comparison::Gte,
+            proto::Comparison::Unspecified => return Err(-1)
+        };
+
+        options.filters.push(Filter::new(field, comparison, filter.value));
+    }
+
+    options.sort = to_field(request.sort)?;
+
+    options.direction = match proto::Direction::try_from(request.order).map_err(|_| -1)? {
+        proto::Direction::Asc => Direction::Asc,
+        proto::Direction::Desc => Direction::Desc
+    };
+
+    options.offset = request.offset as usize;
+    options.limit = request.limit.map(|limit| limit as usize);
+
+    Ok(options)
+}
+
+fn to_field(value: i32) -> Result<Option<Field>, i32> {
+    match proto::Field::try_from(value).map_err(|_| -1)? {
+        proto::Field::Unspecified => Ok(None),
+        proto::Field::Id => Ok(Some(Field::Id)),
+        proto::Field::Name => Ok(Some(Field::Name)),
+        proto::Field::Cal => Ok(Some(Field::Cal)),
+        proto::Field::Size => Ok(Some(Field::Size)),
+        proto::Field::Sodium => Ok(Some(Field::Sodium)),
+        proto::Field::Sugar => Ok(Some(Field::Sugar))
+    }
+}
+
+fn to_policy(value: i32) -> Result<MultiItemPolicy, i32> {
+    match proto::MultiItemPolicy::try_from(value).map_err(|_| -1)? {
+        proto::MultiItemPolicy::Sum => Ok(MultiItemPolicy::Sum),
+        proto::MultiItemPolicy::Reject => Ok(MultiItemPolicy::Reject),
+        proto::MultiItemPolicy::Confirm => Ok(MultiItemPolicy::Confirm)
+    }
+}
+
+// expected_version works like If-Match: updates without it are unconditional
+fn precondition(expected_version: Option<u64>) -> impl Fn(u64) -> bool {
+    move |version| expected_version.is_none_or(|expected| expected == version)
+}
+
+// Status closest to the one the /v2 routes answer with, carrying the same message and the error
+// code in the error-code metadata
+fn status(err_id: i32) -> Status {
+    let err = ApiError::new(err_id);
+
+    let code = match err_id {
+        -1 | -3 | -6 | -7 | -10 | -15 => Code::InvalidArgument,
+        -2 | -11 => Code::AlreadyExists,
+        -4 => Code::Unavailable,
+        -5 => Code::NotFound,
+        -9 | -16 | -17 => Code::FailedPrecondition,
+        -12 => Code::Unauthenticated,
+        -13 => Code::PermissionDenied,
+        -8 | -14 => Code::ResourceExhausted,
+        _ => Code::Internal
+    };
+
+    let mut metadata = MetadataMap::new();
+
+    metadata.insert("error-code", MetadataValue::from(err_id));
+
+    Status::with_metadata(code, err.message(), metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use tonic::Code;
+
+    use super::status;
+
+    #[test]
+    fn maps_every_error_code_to_a_status() {
+        let codes: Vec<(i32, Code)> = (-17..=-1).map(|code| (code, status(code).code())).collect();
+
+        assert!(codes.iter().all(|(_, status)| *status != Code::Internal), "{:?}", codes);
+    }
+
+    #[test]
+    fn carries_the_message_and_error_code() {
+        let status = status(-17);
+
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(status.message(), "The last admin API key can't be revoked");
+        assert_eq!(status.metadata().get("error-code").unwrap(), "-17");
+    }
+}
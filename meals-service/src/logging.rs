@@ -0,0 +1,41 @@
+use std::io::Write;
+
+use env_logger::{Builder, Env};
+
+use serde_json::json;
+
+use crate::telemetry;
+
+tokio::task_local! {
+    // ID of the HTTP request being handled, set by api::request_id for as long as it runs
+    static REQUEST_ID: String;
+}
+
+// Writes one JSON object per line. The level comes from RUST_LOG, e.g. "warn" or
+// "info,meals_service=debug", and defaults to info
+pub fn init() {
+    Builder::from_env(Env::default().default_filter_or("info"))
+        .format(|buf, record| {
+            let line = json!({
+                "timestamp": buf.timestamp_millis().to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+                "request_id": request_id(),
+                "trace_id": telemetry::current().map(|context| context.trace_id())
+            });
+
+            writeln!(buf, "{}", line)
+        })
+        .init();
+}
+
+// Runs the future with the ID attached to every line it logs
+pub async fn with_request_id<F: std::future::Future>(id: String, future: F) -> F::Output {
+    REQUEST_ID.scope(id, future).await
+}
+
+// ID of the request being handled, or None outside of one, e.g. in gRPC calls and webhook deliveries
+pub fn request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
@@ -1,33 +1,39 @@
 mod api;
 
-use api::dish::{
-    rebuild, // Delete
-    get_dishes,
-    create_dish,
-    delete_dishes,
-    get_dish,
-    delete_dish
-};
+mod repository;
 
-use api::meal::{
-    create_meal,
-    get_meals,
-    delete_meals,
-    get_meal,
-    delete_meal,
-    update_meal
-};
+mod grpc;
 
-mod repository;
+mod logging;
+
+mod telemetry;
+
+mod shutdown;
+
+use repository::{state::AppState, tenants::{Quota, is_valid_name, DEFAULT_TENANT}, health::Readiness};
+
+use api::idempotency::{IdempotencyStore, idempotency};
+
+use api::auth::authenticate;
+
+use api::metrics::measure;
+
+use api::request_id;
+
+use api::trace::trace;
+
+use repository::webhook_client;
+
+use futures::future::{select, try_join, Either};
 
-use repository::state::AppState;
+use tokio::sync::oneshot;
 
 use actix_web::{
     get,
     HttpServer,
     App,
     web::Data,
-    middleware::Logger,
+    middleware::from_fn,
     Responder,
     HttpResponse
 };
@@ -39,33 +45,154 @@ async fn health_check() -> impl Responder {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    std::env::set_var("RUST_LOG", "debug");
-    std::env::set_var("RUST_BACKTRACE", "1");
-    env_logger::init();
+    logging::init();
+
+    telemetry::init().map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("OTEL_TRACES_EXPORTER: {}", err)))?;
 
     let app_state = Data::new(AppState::new());
 
-    // Boilerplate code taken from Actix Web docs: https://actix.rs/docs/getting-started
-    HttpServer::new(move || {
-        let logger = Logger::default();
+    app_state.set_default_quota(Quota::from_env());
+
+    // Requests never add tenants, so the ones besides the default tenant are listed in TENANTS,
+    // comma-separated, or added by an admin with PUT /tenants/{name}
+    app_state.create_tenant(DEFAULT_TENANT);
+
+    if let Ok(config) = std::env::var("TENANTS") {
+        for name in config.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            if !is_valid_name(name) {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("TENANTS: \"{}\" is not a valid tenant name", name)))
+            }
+
+            app_state.create_tenant(name);
+        }
+    }
+
+    // Comma-separated <role>:<hex SHA-256 of the key>[:<name>[:<tenant>]], where the name may be
+    // left empty, e.g. reader:<hash>::acme. Keys without a tenant are bound to the default one
+    if let Ok(config) = std::env::var("API_KEYS") {
+        app_state.api_keys().load(&config).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("API_KEYS: {}", err)))?;
+    }
+
+    // JWTs issued by the gateway are verified against HS256 secrets and the RS256/ES256 public keys
+    // of a JWKS file. Without any, only API keys are accepted
+    if let Ok(config) = std::env::var("JWT_SECRETS") {
+        app_state.jwt().load_secrets(&config).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("JWT_SECRETS: {}", err)))?;
+    }
+
+    if let Ok(path) = std::env::var("JWT_JWKS_FILE") {
+        let jwks = std::fs::read_to_string(&path)?;
+
+        app_state.jwt().load_jwks(&jwks).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("JWT_JWKS_FILE: {}", err)))?;
+    }
+
+    if let Ok(config) = std::env::var("JWT_AUDIENCE") {
+        app_state.jwt().set_audience(&config);
+    }
+
+    // Required by default once keys are configured. Revoking keys later doesn't turn it off, and it
+    // can't be required without an admin key or a JWT key to bootstrap access with
+    let auth_required = match std::env::var("AUTH_REQUIRED").as_deref() {
+        Ok("true") => true,
+        Ok("false") => false,
+        Ok(other) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("AUTH_REQUIRED: expected true or false, got {}", other))),
+        Err(_) => app_state.api_keys().has_admin() || app_state.jwt().is_enabled()
+    };
+
+    if auth_required && !app_state.api_keys().has_admin() && !app_state.jwt().is_enabled() {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "AUTH_REQUIRED: needs an admin key in API_KEYS, or JWT_SECRETS or JWT_JWKS_FILE"));
+    }
+
+    app_state.set_auth_required(auth_required);
 
+    let idempotency_store = Data::new(IdempotencyStore::from_env());
+
+    let dispatcher = actix_web::rt::spawn(webhook_client::dispatch(app_state.clone().into_inner()));
+
+    let grace_period = shutdown::grace_period();
+    let drain_delay = shutdown::drain_delay();
+
+    let (stop_grpc, grpc_stopped) = oneshot::channel::<()>();
+
+    let grpc_server = grpc::serve(app_state.clone().into_inner(), grpc::port(), async {
+        let _ = grpc_stopped.await;
+    });
+
+    let server_state = app_state.clone();
+
+    // Boilerplate code taken from Actix Web docs: https://actix.rs/docs/getting-started
+    let http_server = HttpServer::new(move || {
         App::new()
-            .wrap(logger)
-            .app_data(app_state.clone())
+            .wrap(from_fn(idempotency))
+            .wrap(from_fn(authenticate))
+            .wrap(from_fn(measure))
+            .wrap(from_fn(request_id::assign))
+            .wrap(from_fn(trace))
+            .app_data(server_state.clone())
+            .app_data(idempotency_store.clone())
             .service(health_check)
-            .service(get_dishes)
-            .service(create_dish)
-            .service(delete_dishes)
-            .service(get_dish)
-            .service(delete_dish)
-            .service(create_meal)
-            .service(get_meals)
-            .service(delete_meals)
-            .service(get_meal)
-            .service(delete_meal)
-            .service(update_meal)
+            .configure(api::configure)
     })
     .bind(("0.0.0.0", 8000))?
-    .run()
-    .await
+    // Signals are handled below, so that both servers stop together
+    .disable_signals()
+    .shutdown_timeout(grace_period.as_secs())
+    .run();
+
+    let http_handle = http_server.handle();
+
+    // Both run until a signal arrives, and a failure of either one stops the process
+    let mut servers = Box::pin(try_join(http_server, grpc_server));
+
+    let signal = match select(&mut servers, Box::pin(shutdown::signal())).await {
+        Either::Left((result, _)) => return result.map(|_| ()),
+        Either::Right((signal, _)) => signal
+    };
+
+    log::info!("Received {}, draining for {}s before waiting up to {}s more for requests in progress", signal, drain_delay.as_secs(), (grace_period - drain_delay).as_secs());
+
+    let deadline = tokio::time::Instant::now() + grace_period;
+
+    // Load balancers polling /health/ready stop sending requests while the listeners are still
+    // open, and event streams end so their connections don't hold up the servers
+    app_state.set_readiness(Readiness::Draining);
+    app_state.events().end_streams();
+
+    actix_web::rt::time::sleep(drain_delay).await;
+
+    log::info!("No longer accepting connections");
+
+    let _ = stop_grpc.send(());
+
+    actix_web::rt::spawn(http_handle.stop(true));
+
+    let drained = tokio::time::timeout_at(deadline, async {
+        let result = servers.await;
+
+        app_state.in_flight().wait_idle().await;
+
+        result
+    }).await;
+
+    match drained {
+        Ok(Ok(_)) => log::info!("All requests in progress finished"),
+        Ok(Err(err)) => log::error!("A server failed while shutting down: {}", err),
+        Err(_) => log::warn!("Grace period elapsed with requests still running, {} of them waiting on the nutrition API", app_state.in_flight().count())
+    }
+
+    // Changes made while draining were published to the webhook dispatcher too, so it only stops
+    // once the servers have, and gets whatever is left of the grace period for its deliveries
+    app_state.events().close();
+
+    match tokio::time::timeout_at(deadline, dispatcher).await {
+        Ok(_) => log::info!("All webhook deliveries finished"),
+        Err(_) => log::warn!("Grace period elapsed with webhook deliveries still pending")
+    }
+
+    // Dishes and meals are only kept in memory, so the spans waiting to be exported are all there
+    // is to flush
+    telemetry::flush().await;
+
+    log::info!("Shutdown complete");
+
+    Ok(())
 }
@@ -0,0 +1,188 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex
+};
+
+use rand::RngCore;
+
+use serde::{Serialize, Deserialize};
+
+use sha2::{Digest, Sha256};
+
+use strum_macros::{Display, EnumString};
+
+use utoipa::ToSchema;
+
+use super::{webhooks::now, tenants::is_valid_name};
+
+// Each role may do everything the ones before it may
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema, EnumString, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Role {
+    // Reads dishes and meals
+    Reader,
+    // Also creates, updates and deletes them
+    Editor,
+    // Also manages API keys and webhooks
+    Admin
+}
+
+// What a credential allows: a role, and the tenant it is bound to, if not the default one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grant {
+    pub role: Role,
+    pub tenant: Option<String>,
+    // Who the credential belongs to, e.g. "key:3" or "jwt:<sub>", so that what one caller did
+    // can't be handed to another
+    pub principal: String
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ApiKey {
+    #[serde(rename = "ID")]
+    id: i32,
+    role: Role,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    // Only tenant that keys below admin may use, the default one when missing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tenant: Option<String>,
+    // Only returned when the key is created, since only its hash is kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    created: u64,
+    #[serde(skip)]
+    hash: String
+}
+
+// API keys by ID. Whether they are required at all is up to AppState::is_auth_required
+#[derive(Default)]
+pub struct ApiKeyRegistry {
+    counter: Mutex<i32>,
+    keys: Mutex<HashMap<i32, ApiKey>>
+}
+
+impl ApiKeyRegistry {
+    // Adds the keys given as comma-separated <role>:<hex SHA-256 of the key>[:<name>[:<tenant>]]
+    // entries, as in the API_KEYS environment variable, where the name may be left empty. Nothing is
+    // added if any entry is invalid
+    pub fn load(&self, config: &str) -> Result<usize, String> {
+        let mut entries = Vec::new();
+
+        for entry in config.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let mut parts = entry.splitn(4, ':');
+
+            let role = parts.next().unwrap_or_default();
+            let role = role.parse::<Role>().map_err(|_| format!("unknown role \"{}\"", role))?;
+
+            let hash = parts.next().unwrap_or_default().to_lowercase();
+
+            if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("\"{}\" is not a hex SHA-256 hash", hash))
+            }
+
+            let name = parts.next().filter(|name| !name.is_empty()).map(String::from);
+            let tenant = parts.next().map(String::from);
+
+            if let Some(tenant) = tenant.as_deref().filter(|tenant| !is_valid_name(tenant)) {
+                return Err(format!("\"{}\" is not a valid tenant name", tenant))
+            }
+
+            entries.push((role, hash, name, tenant));
+        }
+
+        let count = entries.len();
+
+        for (role, hash, name, tenant) in entries {
+            self.insert(role, hash, name, tenant);
+        }
+
+        Ok(count)
+    }
+
+    // Generates a new key, returned in full this once
+    pub fn create(&self, role: Role, name: Option<String>, tenant: Option<String>) -> Result<ApiKey, i32> {
+        if name.as_ref().is_some_and(|name| name.trim().is_empty()) {
+            return Err(-1)
+        }
+
+        if tenant.as_deref().is_some_and(|tenant| !is_valid_name(tenant)) {
+            return Err(-15)
+        }
+
+        let mut bytes = [0u8; 32];
+
+        rand::thread_rng().fill_bytes(&mut bytes);
+
+        let secret = hex::encode(bytes);
+
+        let key = self.insert(role, hash(&secret), name, tenant);
+
+        Ok(ApiKey { key: Some(secret), ..key })
+    }
+
+    fn insert(&self, role: Role, hash: String, name: Option<String>, tenant: Option<String>) -> ApiKey {
+        let mut counter = self.counter.lock().unwrap();
+        *counter += 1;
+
+        let key = ApiKey {
+            id: *counter,
+            role,
+            name,
+            tenant,
+            key: None,
+            created: now(),
+            hash
+        };
+
+        self.keys.lock().unwrap().insert(key.id, key.clone());
+
+        key
+    }
+
+    pub fn get_keys(&self) -> Vec<ApiKey> {
+        let mut keys: Vec<ApiKey> = self.keys.lock().unwrap().values().cloned().collect();
+
+        keys.sort_by_key(|key| key.id);
+
+        keys
+    }
+
+    // Revokes the key unless it is the last admin key (-17), which would leave nobody able to
+    // manage keys
+    pub fn revoke(&self, id: i32) -> Result<i32, i32> {
+        let mut keys = self.keys.lock().unwrap();
+
+        let role = match keys.get(&id) {
+            Some(key) => key.role,
+            None => return Err(-5)
+        };
+
+        if role == Role::Admin && keys.values().filter(|key| key.role == Role::Admin).count() == 1 {
+            return Err(-17)
+        }
+
+        keys.remove(&id);
+
+        Ok(id)
+    }
+
+    pub fn has_admin(&self) -> bool {
+        self.keys.lock().unwrap().values().any(|key| key.role == Role::Admin)
+    }
+
+    // What the key grants, or None when it isn't one of ours
+    pub fn authenticate(&self, key: &str) -> Option<Grant> {
+        let hash = hash(key);
+
+        self.keys.lock().unwrap()
+            .values()
+            .find(|key| key.hash == hash)
+            .map(|key| Grant { role: key.role, tenant: key.tenant.clone(), principal: format!("key:{}", key.id) })
+    }
+}
+
+fn hash(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
@@ -0,0 +1,199 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant}
+};
+
+use serde::Serialize;
+
+use utoipa::ToSchema;
+
+// Consecutive failed calls to the nutrition API after which it isn't called for a while
+const FAILURE_THRESHOLD: u32 = 5;
+
+// How long calls fail right away once the breaker opens, before a single call is let through again
+const OPEN_DURATION: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum BreakerState {
+    // Calls go through
+    Closed,
+    // Calls fail with -4 without reaching the API
+    Open,
+    // The next call is let through to find out whether the API is back
+    HalfOpen
+}
+
+struct Breaker {
+    failures: u32,
+    opened: Option<Instant>,
+    // When the call let through while half-open started. Another one is let through if it never
+    // reports back, e.g. because its request was dropped
+    trial: Option<Instant>
+}
+
+// Stops calling the nutrition API while it keeps failing, so requests get -4 at once instead of
+// each waiting for it to time out
+pub struct CircuitBreaker {
+    threshold: u32,
+    open_duration: Duration,
+    breaker: Mutex<Breaker>
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        CircuitBreaker::new(FAILURE_THRESHOLD, OPEN_DURATION)
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, open_duration: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            threshold,
+            open_duration,
+            breaker: Mutex::new(Breaker { failures: 0, opened: None, trial: None })
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        let breaker = self.breaker.lock().unwrap();
+
+        match breaker.opened {
+            None => BreakerState::Closed,
+            Some(opened) if opened.elapsed() < self.open_duration => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen
+        }
+    }
+
+    // Whether a call may be made now. Every call allowed must be followed by record
+    pub fn allow(&self) -> bool {
+        let mut breaker = self.breaker.lock().unwrap();
+
+        match breaker.opened {
+            None => true,
+            Some(opened) if opened.elapsed() < self.open_duration => false,
+            Some(_) => match breaker.trial {
+                Some(started) if started.elapsed() < self.open_duration => false,
+                _ => {
+                    breaker.trial = Some(Instant::now());
+
+                    true
+                }
+            }
+        }
+    }
+
+    // A success closes the breaker. Failures open it once there have been `threshold` in a row, or
+    // right away for the call let through while half-open
+    pub fn record(&self, success: bool) {
+        let mut breaker = self.breaker.lock().unwrap();
+
+        if success {
+            *breaker = Breaker { failures: 0, opened: None, trial: None };
+
+            return
+        }
+
+        breaker.failures += 1;
+
+        if breaker.trial.is_some() || breaker.failures >= self.threshold {
+            if breaker.opened.is_none() {
+                log::warn!("Nutrition API failed {} times in a row, calls fail with -4 for the next {} seconds", breaker.failures, self.open_duration.as_secs());
+            }
+
+            breaker.opened = Some(Instant::now());
+            breaker.trial = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread::sleep, time::Duration};
+
+    use super::{BreakerState, CircuitBreaker};
+
+    const OPEN_DURATION: Duration = Duration::from_millis(50);
+
+    fn failing(failures: u32) -> CircuitBreaker {
+        let breaker = CircuitBreaker::new(3, OPEN_DURATION);
+
+        for _ in 0..failures {
+            assert!(breaker.allow());
+
+            breaker.record(false);
+        }
+
+        breaker
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        assert_eq!(failing(2).state(), BreakerState::Closed);
+        assert!(failing(2).allow());
+
+        let breaker = failing(3);
+
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn successes_reset_the_count() {
+        let breaker = failing(2);
+
+        breaker.record(true);
+        breaker.record(false);
+        breaker.record(false);
+
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+
+    #[test]
+    fn lets_a_single_call_through_once_half_open() {
+        let breaker = failing(3);
+
+        sleep(OPEN_DURATION);
+
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+
+        breaker.record(true);
+
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(breaker.allow());
+    }
+
+    #[test]
+    fn reopens_when_the_trial_call_fails() {
+        let breaker = failing(3);
+
+        sleep(OPEN_DURATION);
+
+        assert!(breaker.allow());
+
+        breaker.record(false);
+
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn lets_another_call_through_when_the_trial_never_reports() {
+        let breaker = failing(3);
+
+        sleep(OPEN_DURATION);
+
+        assert!(breaker.allow());
+
+        sleep(OPEN_DURATION);
+
+        assert!(breaker.allow());
+    }
+
+    #[test]
+    fn states_are_named_in_kebab_case() {
+        assert_eq!(serde_json::to_string(&BreakerState::HalfOpen).unwrap(), r#""half-open""#);
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    collections::VecDeque,
+    sync::Mutex
+};
+
+use serde::Serialize;
+
+use strum_macros::Display;
+
+use tokio::sync::{broadcast, watch};
+
+// Events kept for clients resuming with Last-Event-ID. Older ones are dropped as new ones arrive
+const BUFFER_SIZE: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Resource {
+    Dish,
+    Meal
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Action {
+    Created,
+    Updated,
+    Deleted
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub id: u64,
+    // Tenant whose dish or meal changed. Only its own clients and webhooks see the event
+    pub tenant: String,
+    pub resource: Resource,
+    pub action: Action,
+    // JSON text of the dish or meal after the change, or just its ID once deleted. Kept as text
+    // because converting to a Value would widen f32 fields (251.4 to 251.39999389648438)
+    pub data: String
+}
+
+impl Event {
+    // Name used for the SSE event field, e.g. dish.created
+    pub fn name(&self) -> String {
+        format!("{}.{}", self.resource, self.action)
+    }
+}
+
+// Events a resuming client missed
+pub struct Missed {
+    pub events: Vec<Event>,
+    // False when some of the events after the client's ID are no longer buffered
+    pub complete: bool,
+    // ID of the latest event published
+    pub last_id: u64
+}
+
+struct Buffer {
+    last_id: u64,
+    events: VecDeque<Event>
+}
+
+// Changes to dishes and meals of every tenant in the order they were made. IDs start at 1 and
+// only grow
+pub struct EventLog {
+    buffer: Mutex<Buffer>,
+    // None once closed, after the servers have stopped and nothing more can be published
+    sender: Mutex<Option<broadcast::Sender<Event>>>,
+    // Set when a shutdown begins, ending the /events streams while webhooks still get every event
+    streams_ended: watch::Sender<bool>
+}
+
+impl Default for EventLog {
+    fn default() -> EventLog {
+        let (sender, _) = broadcast::channel(BUFFER_SIZE);
+
+        EventLog {
+            buffer: Mutex::new(Buffer { last_id: 0, events: VecDeque::with_capacity(BUFFER_SIZE) }),
+            sender: Mutex::new(Some(sender)),
+            streams_ended: watch::Sender::new(false)
+        }
+    }
+}
+
+impl EventLog {
+    pub fn publish<T: Serialize>(&self, tenant: &str, resource: Resource, action: Action, data: &T) {
+        let mut buffer = self.buffer.lock().unwrap();
+
+        buffer.last_id += 1;
+
+        let event = Event {
+            id: buffer.last_id,
+            tenant: tenant.to_string(),
+            resource,
+            action,
+            data: serde_json::to_string(data).unwrap_or_default()
+        };
+
+        if buffer.events.len() == BUFFER_SIZE {
+            buffer.events.pop_front();
+        }
+
+        buffer.events.push_back(event.clone());
+
+        // Fails only when nobody is listening
+        if let Some(sender) = self.sender.lock().unwrap().as_ref() {
+            let _ = sender.send(event);
+        }
+    }
+
+    // Buffered events after `last_id` together with a receiver for everything published later.
+    // Both are taken under the same lock so that no event is missed or sent twice
+    pub fn subscribe(&self, last_id: Option<u64>) -> (Missed, broadcast::Receiver<Event>) {
+        let buffer = self.buffer.lock().unwrap();
+
+        let missed = match last_id {
+            Some(last_id) => {
+                let oldest = buffer.events.front().map_or(buffer.last_id + 1, |event| event.id);
+
+                Missed {
+                    events: buffer.events.iter().filter(|event| event.id > last_id).cloned().collect(),
+                    // IDs newer than any event come from before a restart, which starts again at 1
+                    complete: last_id + 1 >= oldest && last_id <= buffer.last_id,
+                    last_id: buffer.last_id
+                }
+            },
+            None => Missed { events: Vec::new(), complete: true, last_id: buffer.last_id }
+        };
+
+        let receiver = match self.sender.lock().unwrap().as_ref() {
+            Some(sender) => sender.subscribe(),
+            None => broadcast::channel(1).1
+        };
+
+        (missed, receiver)
+    }
+
+    // Ends every live /events stream, and the ones started later right after the buffered events,
+    // so that open connections don't hold up a shutdown
+    pub fn end_streams(&self) {
+        self.streams_ended.send_replace(true);
+    }
+
+    pub fn streams_ended(&self) -> watch::Receiver<bool> {
+        self.streams_ended.subscribe()
+    }
+
+    // Ends the subscriptions to live events, once everything has been published. The webhook
+    // dispatcher then finishes the deliveries it has started and returns
+    pub fn close(&self) {
+        self.sender.lock().unwrap().take();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    use super::{Action, EventLog, Resource, BUFFER_SIZE};
+
+    fn log_with(count: usize) -> EventLog {
+        let log = EventLog::default();
+
+        for id in 1..=count {
+            log.publish("default", Resource::Dish, Action::Created, &json!({ "ID": id }));
+        }
+
+        log
+    }
+
+    fn ids(log: &EventLog, last_id: Option<u64>) -> (Vec<u64>, bool) {
+        let (missed, _) = log.subscribe(last_id);
+
+        (missed.events.iter().map(|event| event.id).collect(), missed.complete)
+    }
+
+    #[test]
+    fn new_clients_only_get_live_events() {
+        assert_eq!(ids(&log_with(3), None), (vec![], true));
+    }
+
+    #[test]
+    fn resuming_clients_get_the_events_after_their_id() {
+        let log = log_with(3);
+
+        assert_eq!(ids(&log, Some(1)), (vec![2, 3], true));
+        assert_eq!(ids(&log, Some(3)), (vec![], true));
+        assert_eq!(ids(&log, Some(0)), (vec![1, 2, 3], true));
+    }
+
+    #[test]
+    fn live_events_follow_the_buffered_ones() {
+        let log = log_with(2);
+        let (missed, mut receiver) = log.subscribe(Some(1));
+
+        log.publish("other", Resource::Meal, Action::Deleted, &json!({ "ID": 1 }));
+
+        let event = receiver.try_recv().unwrap();
+
+        assert_eq!(missed.last_id, 2);
+        assert_eq!((event.id, event.tenant.as_str(), event.name(), event.data.as_str()), (3, "other", String::from("meal.deleted"), r#"{"ID":1}"#));
+    }
+
+    #[test]
+    fn only_the_latest_events_are_kept() {
+        let log = log_with(BUFFER_SIZE + 10);
+
+        let (events, complete) = ids(&log, Some(9));
+
+        assert!(!complete);
+        assert_eq!((events.len(), events[0]), (BUFFER_SIZE, 11));
+
+        assert!(ids(&log, Some(10)).1);
+    }
+
+    #[test]
+    fn ids_from_before_a_restart_are_incomplete() {
+        let (missed, _) = log_with(3).subscribe(Some(500));
+
+        assert!(!missed.complete);
+        assert_eq!(missed.last_id, 3);
+    }
+
+    #[test]
+    fn closing_ends_subscriptions() {
+        let log = log_with(1);
+        let (_, mut receiver) = log.subscribe(None);
+
+        log.close();
+
+        assert_eq!(receiver.try_recv().unwrap_err(), TryRecvError::Closed);
+        assert_eq!(log.subscribe(None).1.try_recv().unwrap_err(), TryRecvError::Closed);
+    }
+}
@@ -0,0 +1,93 @@
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use strum_macros::Display;
+
+use tokio::sync::Mutex;
+
+use utoipa::ToSchema;
+
+use super::{
+    nutrition_api_client::probe,
+    circuit_breaker::{BreakerState, CircuitBreaker}
+};
+
+// How long the result of probing the nutrition API is reused, so that polling
+// /health/dependencies doesn't call the API on every request
+const PROBE_TTL: Duration = Duration::from_secs(10);
+
+// Whether the service should be sent traffic. Connections are only accepted once the
+// configuration has been loaded, so there is no state before Ready
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Display, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Readiness {
+    Ready,
+    // Shutting down and finishing the requests already running
+    Draining
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Up,
+    Down
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct NutritionApiHealth {
+    pub status: Status,
+    // Time taken by the probe to get any HTTP response
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // State of the breaker in front of the calls made for dishes, as it is now rather than when probed
+    pub circuit_breaker: BreakerState
+}
+
+// Dishes and meals are only kept in memory, so there is nothing that could be down
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct StorageHealth {
+    pub kind: String,
+    pub dishes: usize,
+    pub meals: usize
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DependencyHealth {
+    // Same as the nutrition API's, the only dependency that can be down
+    pub status: Status,
+    pub readiness: Readiness,
+    pub nutrition_api: NutritionApiHealth,
+    pub storage: StorageHealth
+}
+
+// Last result of probing the nutrition API
+#[derive(Default)]
+pub struct NutritionApiProbe {
+    last: Mutex<Option<(Instant, NutritionApiHealth)>>
+}
+
+impl NutritionApiProbe {
+    // The last result while it is younger than PROBE_TTL, or else a new one. Requests arriving while
+    // the API is being probed wait for that probe instead of starting their own
+    pub async fn check(&self, breaker: &CircuitBreaker) -> NutritionApiHealth {
+        let mut last = self.last.lock().await;
+
+        if let Some((checked, health)) = last.as_ref() {
+            if checked.elapsed() < PROBE_TTL {
+                return NutritionApiHealth { circuit_breaker: breaker.state(), ..health.clone() }
+            }
+        }
+
+        let health = match probe().await {
+            Ok(latency) => NutritionApiHealth { status: Status::Up, latency_ms: latency.as_millis() as u64, error: None, circuit_breaker: breaker.state() },
+            Err(err) => NutritionApiHealth { status: Status::Down, latency_ms: 0, error: Some(err), circuit_breaker: breaker.state() }
+        };
+
+        *last = Some((Instant::now(), health.clone()));
+
+        health
+    }
+}
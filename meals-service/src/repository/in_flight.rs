@@ -0,0 +1,40 @@
+use tokio::sync::watch;
+
+// Calls to the nutrition API still running, so a shutdown can wait for the dishes being created or
+// updated with them
+pub struct InFlight {
+    count: watch::Sender<usize>
+}
+
+// Counted until dropped
+pub struct Call<'a> {
+    in_flight: &'a InFlight
+}
+
+impl Default for InFlight {
+    fn default() -> Self {
+        InFlight { count: watch::channel(0).0 }
+    }
+}
+
+impl InFlight {
+    pub fn begin(&self) -> Call<'_> {
+        self.count.send_modify(|count| *count += 1);
+
+        Call { in_flight: self }
+    }
+
+    pub fn count(&self) -> usize {
+        *self.count.borrow()
+    }
+
+    pub async fn wait_idle(&self) {
+        let _ = self.count.subscribe().wait_for(|count| *count == 0).await;
+    }
+}
+
+impl Drop for Call<'_> {
+    fn drop(&mut self) {
+        self.in_flight.count.send_modify(|count| *count -= 1);
+    }
+}
@@ -0,0 +1,226 @@
+use std::sync::Mutex;
+
+use jsonwebtoken::{
+    decode,
+    decode_header,
+    jwk::{AlgorithmParameters, EllipticCurve, JwkSet, KeyAlgorithm},
+    Algorithm,
+    DecodingKey,
+    Validation
+};
+
+use serde::Deserialize;
+
+use sha2::{Digest, Sha256};
+
+use super::api_keys::{Grant, Role};
+
+// Key a token may be signed with, and the algorithm it is for. Tokens must name the same
+// algorithm in their header, so a public key can't be used as an HMAC secret
+struct VerifyingKey {
+    id: Option<String>,
+    algorithm: Algorithm,
+    key: DecodingKey
+}
+
+// Claims the permissions of a token are read from. Every valid token may read, while writing and
+// administration have to be granted by the role claim or by a scope. A tenant claim confines the
+// token to that tenant's dishes and meals
+#[derive(Deserialize)]
+struct Claims {
+    role: Option<String>,
+    #[serde(default)]
+    scope: String,
+    tenant: Option<String>,
+    sub: Option<String>
+}
+
+impl Claims {
+    fn role(&self) -> Role {
+        let scopes = self.scope.split_whitespace().filter_map(|scope| match scope {
+            "meals:read" => Some(Role::Reader),
+            "meals:write" => Some(Role::Editor),
+            "meals:admin" => Some(Role::Admin),
+            _ => None
+        });
+
+        let role = self.role.as_ref().and_then(|role| role.parse::<Role>().ok());
+
+        scopes.chain(role).max().unwrap_or(Role::Reader)
+    }
+}
+
+// Verifies JWT bearer tokens issued by the gateway against locally configured HS256 secrets and
+// RS256/ES256 public keys. Disabled until a secret or key is loaded
+#[derive(Default)]
+pub struct JwtVerifier {
+    keys: Mutex<Vec<VerifyingKey>>,
+    audience: Mutex<Vec<String>>
+}
+
+impl JwtVerifier {
+    // Adds HS256 secrets given as a comma-separated list, as in the JWT_SECRETS environment variable
+    pub fn load_secrets(&self, config: &str) -> Result<usize, String> {
+        let secrets: Vec<&str> = config.split(',').map(str::trim).filter(|secret| !secret.is_empty()).collect();
+
+        let mut keys = self.keys.lock().unwrap();
+
+        for secret in &secrets {
+            keys.push(VerifyingKey {
+                id: None,
+                algorithm: Algorithm::HS256,
+                key: DecodingKey::from_secret(secret.as_bytes())
+            });
+        }
+
+        Ok(secrets.len())
+    }
+
+    // Adds the RSA and P-256 public keys of a JWKS document, as in the file named by JWT_JWKS_FILE.
+    // Keys without an alg are used for RS256 or ES256 according to their type. Nothing is added
+    // if any key is unsupported
+    pub fn load_jwks(&self, jwks: &str) -> Result<usize, String> {
+        let set: JwkSet = serde_json::from_str(jwks).map_err(|err| format!("not a JWKS document: {}", err))?;
+
+        let mut entries = Vec::new();
+
+        for jwk in &set.keys {
+            let algorithm = match (&jwk.algorithm, &jwk.common.key_algorithm) {
+                (AlgorithmParameters::RSA(_), None | Some(KeyAlgorithm::RS256)) => Algorithm::RS256,
+                (AlgorithmParameters::EllipticCurve(params), None | Some(KeyAlgorithm::ES256)) if params.curve == EllipticCurve::P256 => Algorithm::ES256,
+                _ => return Err(format!("key {} is not an RS256 or ES256 public key", jwk.common.key_id.as_deref().unwrap_or("without kid")))
+            };
+
+            let key = DecodingKey::from_jwk(jwk).map_err(|err| err.to_string())?;
+
+            entries.push(VerifyingKey {
+                id: jwk.common.key_id.clone(),
+                algorithm,
+                key
+            });
+        }
+
+        let count = entries.len();
+
+        self.keys.lock().unwrap().extend(entries);
+
+        Ok(count)
+    }
+
+    // Audiences a token's aud claim must include one of, as in the comma-separated JWT_AUDIENCE
+    // environment variable. Tokens of any audience are accepted while there are none
+    pub fn set_audience(&self, config: &str) {
+        *self.audience.lock().unwrap() = config.split(',').map(str::trim).filter(|aud| !aud.is_empty()).map(String::from).collect();
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.lock().unwrap().is_empty()
+    }
+
+    // Role and tenant granted by a token whose signature, expiry, not-before time and audience are
+    // valid, or None otherwise. A kid in the header restricts the keys tried to the one with that ID
+    pub fn verify(&self, token: &str) -> Option<Grant> {
+        let header = decode_header(token).ok()?;
+
+        let mut validation = Validation::new(header.alg);
+
+        validation.validate_nbf = true;
+
+        let audience = self.audience.lock().unwrap();
+
+        match audience.is_empty() {
+            true => validation.validate_aud = false,
+            false => validation.set_audience(&audience)
+        }
+
+        let keys = self.keys.lock().unwrap();
+
+        keys.iter()
+            .filter(|key| key.algorithm == header.alg)
+            .filter(|key| header.kid.is_none() || key.id.is_none() || key.id == header.kid)
+            .find_map(|key| decode::<Claims>(token, &key.key, &validation).ok())
+            .map(|data| {
+                // Tokens without a subject are told apart by their hash
+                let principal = match &data.claims.sub {
+                    Some(sub) => format!("jwt:{}", sub),
+                    None => format!("jwt#{}", &hex::encode(Sha256::digest(token.as_bytes()))[..16])
+                };
+
+                Grant { role: data.claims.role(), tenant: data.claims.tenant, principal }
+            })
+    }
+}
+
+// JWTs are three base64url segments, which API keys never contain
+pub fn is_jwt(credential: &str) -> bool {
+    credential.split('.').count() == 3
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{encode, Algorithm, EncodingKey, Header, get_current_timestamp};
+
+    use serde_json::{json, Value};
+
+    use super::{JwtVerifier, is_jwt};
+
+    use crate::repository::api_keys::Role;
+
+    const SECRET: &str = "hs-secret";
+
+    fn verifier() -> JwtVerifier {
+        let verifier = JwtVerifier::default();
+
+        verifier.load_secrets(SECRET).unwrap();
+
+        verifier
+    }
+
+    fn token(algorithm: Algorithm, claims: Value) -> String {
+        encode(&Header::new(algorithm), &claims, &EncodingKey::from_secret(SECRET.as_bytes())).unwrap()
+    }
+
+    fn expiring_in(seconds: i64) -> u64 {
+        (get_current_timestamp() as i64 + seconds) as u64
+    }
+
+    #[test]
+    fn accepts_a_valid_token() {
+        let token = token(Algorithm::HS256, json!({"sub": "alice", "scope": "meals:write", "tenant": "acme", "exp": expiring_in(3600)}));
+
+        let grant = verifier().verify(&token).unwrap();
+
+        assert!(is_jwt(&token));
+        assert_eq!(grant.role, Role::Editor);
+        assert_eq!(grant.tenant.as_deref(), Some("acme"));
+        assert_eq!(grant.principal, "jwt:alice");
+    }
+
+    #[test]
+    fn rejects_a_token_signed_with_another_algorithm() {
+        let token = token(Algorithm::HS384, json!({"sub": "alice", "exp": expiring_in(3600)}));
+
+        assert!(verifier().verify(&token).is_none());
+    }
+
+    #[test]
+    fn rejects_an_expired_token() {
+        // Past the default leeway of a minute
+        let token = token(Algorithm::HS256, json!({"sub": "alice", "exp": expiring_in(-3600)}));
+
+        assert!(verifier().verify(&token).is_none());
+    }
+
+    #[test]
+    fn rejects_a_token_for_another_audience() {
+        let verifier = verifier();
+
+        verifier.set_audience("meals-service");
+
+        let other = token(Algorithm::HS256, json!({"sub": "alice", "aud": "billing", "exp": expiring_in(3600)}));
+        let ours = token(Algorithm::HS256, json!({"sub": "alice", "aud": "meals-service", "exp": expiring_in(3600)}));
+
+        assert!(verifier.verify(&other).is_none());
+        assert!(verifier.verify(&ours).is_some());
+    }
+}
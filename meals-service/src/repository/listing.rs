@@ -0,0 +1,229 @@
+use std::cmp::Ordering;
+
+use strum_macros::EnumString;
+
+use async_graphql::Enum;
+
+use super::names::normalize_name;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, Enum)]
+#[strum(serialize_all = "lowercase")]
+pub enum Field {
+    #[strum(serialize = "id", serialize = "ID")]
+    Id,
+    Name,
+    Cal,
+    Size,
+    Sodium,
+    Sugar
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumString, Enum)]
+#[strum(serialize_all = "lowercase")]
+pub enum Comparison {
+    Lt,
+    Lte,
+    Gt,
+    Gte
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, EnumString, Enum)]
+#[strum(serialize_all = "lowercase")]
+pub enum Direction {
+    #[default]
+    Asc,
+    Desc
+}
+
+#[derive(Debug, Clone)]
+pub struct Filter {
+    field: Field,
+    comparison: Comparison,
+    value: f32
+}
+
+impl Filter {
+    pub fn new(field: Field, comparison: Comparison, value: f32) -> Filter {
+        Filter {
+            field,
+            comparison,
+            value
+        }
+    }
+
+    fn matches<T: Listable>(&self, item: &T) -> bool {
+        match item.nutrient(self.field) {
+            Some(v) => match self.comparison {
+                Comparison::Lt => v < self.value,
+                Comparison::Lte => v <= self.value,
+                Comparison::Gt => v > self.value,
+                Comparison::Gte => v >= self.value
+            },
+            None => false
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ListOptions {
+    pub filters: Vec<Filter>,
+    pub sort: Option<Field>,
+    pub direction: Direction,
+    pub offset: usize,
+    pub limit: Option<usize>
+}
+
+pub trait Listable {
+    fn id(&self) -> i32;
+    fn name(&self) -> &str;
+    fn nutrient(&self, field: Field) -> Option<f32>;
+}
+
+// Filters and orders references so that only the requested page has to be cloned by the caller.
+// Returns the number of items matching the filters alongside the page itself
+pub fn select<'a, T: Listable + 'a>(items: impl Iterator<Item = &'a T>, options: &ListOptions) -> (usize, Vec<&'a T>) {
+    let mut matching: Vec<&T> = items
+        .filter(|item| options.filters.iter().all(|filter| filter.matches(*item)))
+        .collect();
+
+    matching.sort_by(|a, b| {
+        let ordering = match options.sort.unwrap_or(Field::Id) {
+            Field::Id => a.id().cmp(&b.id()),
+            Field::Name => normalize_name(a.name()).cmp(&normalize_name(b.name())),
+            field => compare_nutrient(a.nutrient(field), b.nutrient(field))
+        };
+
+        match options.direction {
+            Direction::Asc => ordering.then(a.id().cmp(&b.id())),
+            Direction::Desc => ordering.reverse().then(b.id().cmp(&a.id()))
+        }
+    });
+
+    let total = matching.len();
+
+    let page = matching
+        .into_iter()
+        .skip(options.offset)
+        .take(options.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    (total, page)
+}
+
+fn compare_nutrient(a: Option<f32>, b: Option<f32>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Field, Comparison, Direction, Filter, Listable, ListOptions, select};
+
+    struct Item {
+        id: i32,
+        name: &'static str,
+        cal: f32,
+        // Like meals, which have no serving size
+        size: Option<f32>
+    }
+
+    impl Listable for Item {
+        fn id(&self) -> i32 {
+            self.id
+        }
+
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn nutrient(&self, field: Field) -> Option<f32> {
+            match field {
+                Field::Cal => Some(self.cal),
+                Field::Size => self.size,
+                _ => None
+            }
+        }
+    }
+
+    fn items() -> Vec<Item> {
+        vec![
+            Item { id: 1, name: "pasta", cal: 500.0, size: Some(150.0) },
+            Item { id: 2, name: "Focaccia", cal: 251.4, size: None },
+            Item { id: 3, name: "chicken soup", cal: 33.2, size: Some(100.0) },
+            Item { id: 4, name: "salad", cal: 251.4, size: Some(100.0) }
+        ]
+    }
+
+    fn ids(options: &ListOptions) -> (usize, Vec<i32>) {
+        let items = items();
+        let (total, page) = select(items.iter(), options);
+
+        (total, page.into_iter().map(|item| item.id).collect())
+    }
+
+    #[test]
+    fn lists_every_item_by_id_by_default() {
+        assert_eq!(ids(&ListOptions::default()), (4, vec![1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn keeps_items_matching_every_filter() {
+        let options = ListOptions {
+            filters: vec![Filter::new(Field::Cal, Comparison::Gt, 33.2), Filter::new(Field::Cal, Comparison::Lte, 251.4)],
+            ..Default::default()
+        };
+
+        assert_eq!(ids(&options), (2, vec![2, 4]));
+    }
+
+    #[test]
+    fn filters_never_match_missing_fields() {
+        let options = ListOptions { filters: vec![Filter::new(Field::Size, Comparison::Gte, 0.0)], ..Default::default() };
+
+        assert_eq!(ids(&options), (3, vec![1, 3, 4]));
+
+        let options = ListOptions { filters: vec![Filter::new(Field::Id, Comparison::Gte, 0.0)], ..Default::default() };
+
+        assert_eq!(ids(&options), (0, vec![]));
+    }
+
+    #[test]
+    fn sorts_names_ignoring_case() {
+        let options = ListOptions { sort: Some(Field::Name), ..Default::default() };
+
+        assert_eq!(ids(&options), (4, vec![3, 2, 1, 4]));
+    }
+
+    #[test]
+    fn breaks_ties_by_id_in_the_same_direction() {
+        let options = ListOptions { sort: Some(Field::Cal), ..Default::default() };
+
+        assert_eq!(ids(&options), (4, vec![3, 2, 4, 1]));
+
+        let options = ListOptions { sort: Some(Field::Cal), direction: Direction::Desc, ..Default::default() };
+
+        assert_eq!(ids(&options), (4, vec![1, 4, 2, 3]));
+    }
+
+    #[test]
+    fn sorts_missing_fields_last() {
+        let options = ListOptions { sort: Some(Field::Size), ..Default::default() };
+
+        assert_eq!(ids(&options), (4, vec![3, 4, 1, 2]));
+    }
+
+    #[test]
+    fn pages_count_every_matching_item() {
+        let options = ListOptions { offset: 1, limit: Some(2), ..Default::default() };
+
+        assert_eq!(ids(&options), (4, vec![2, 3]));
+
+        let options = ListOptions { offset: 10, ..Default::default() };
+
+        assert_eq!(ids(&options), (4, vec![]));
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::Mutex,
+    time::Duration
+};
+
+use super::nutrition_api_client::NutritionInformation;
+
+// Upper bounds in seconds, the Prometheus client defaults
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Default)]
+struct Histogram {
+    // Observations at or below each bound, so every bucket includes the ones before it
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+
+        for (bucket, bound) in self.buckets.iter_mut().zip(BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+
+        for (bucket, bound) in self.buckets.iter().zip(BUCKETS) {
+            let _ = writeln!(out, "{}_bucket{{{}{}le=\"{}\"}} {}", name, labels, separator, bound, bucket);
+        }
+
+        let _ = writeln!(out, "{}_bucket{{{}{}le=\"+Inf\"}} {}", name, labels, separator, self.count);
+        let _ = writeln!(out, "{}_sum{} {}", name, braced(labels), self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, braced(labels), self.count);
+    }
+}
+
+// Key of the HTTP series: method, route pattern and status
+type RequestLabels = (String, String, u16);
+
+// Counters and histograms exposed at GET /metrics in the Prometheus text format. Gauges are read
+// from the tenants when rendering instead of being kept here
+#[derive(Default)]
+pub struct Metrics {
+    requests: Mutex<BTreeMap<RequestLabels, Histogram>>,
+    revalidations: Mutex<BTreeMap<&'static str, u64>>,
+    nutrition_calls: Mutex<BTreeMap<&'static str, u64>>,
+    nutrition_latency: Mutex<Histogram>
+}
+
+impl Metrics {
+    // Routes are recorded by pattern, e.g. /dishes/{ID}, so IDs and names don't create new series
+    pub fn record_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.requests.lock().unwrap()
+            .entry((method.to_string(), route.to_string(), status))
+            .or_default()
+            .observe(elapsed);
+    }
+
+    // A GET with If-None-Match is a hit when the client's copy was still current and answered with
+    // 304, and a miss when the resource had to be sent again
+    pub fn record_revalidation(&self, hit: bool) {
+        *self.revalidations.lock().unwrap().entry(if hit { "hit" } else { "miss" }).or_default() += 1;
+    }
+
+    pub fn record_nutrition_call(&self, result: &Result<NutritionInformation, i32>, elapsed: Duration) {
+        let outcome = match result {
+            Ok(_) => "ok",
+            Err(-3) => "unknown_dish",
+            Err(_) => "unavailable"
+        };
+
+        *self.nutrition_calls.lock().unwrap().entry(outcome).or_default() += 1;
+
+        self.nutrition_latency.lock().unwrap().observe(elapsed);
+    }
+
+    pub fn render(&self, dishes: usize, meals: usize) -> String {
+        let mut out = String::new();
+
+        header(&mut out, "http_requests_total", "counter", "HTTP requests by method, route and status");
+
+        let requests = self.requests.lock().unwrap();
+
+        for ((method, route, status), histogram) in requests.iter() {
+            let _ = writeln!(out, "http_requests_total{{{}}} {}", request_labels(method, route, *status), histogram.count);
+        }
+
+        header(&mut out, "http_request_duration_seconds", "histogram", "Time taken to answer HTTP requests by method, route and status");
+
+        for ((method, route, status), histogram) in requests.iter() {
+            histogram.render(&mut out, "http_request_duration_seconds", &request_labels(method, route, *status));
+        }
+
+        drop(requests);
+
+        let revalidations = self.revalidations.lock().unwrap();
+        let hits = revalidations.get("hit").copied().unwrap_or(0);
+        let misses = revalidations.get("miss").copied().unwrap_or(0);
+
+        drop(revalidations);
+
+        header(&mut out, "http_cache_requests_total", "counter", "Conditional GET requests whose ETag was still current (hit) or not (miss)");
+
+        let _ = writeln!(out, "http_cache_requests_total{{result=\"hit\"}} {}", hits);
+        let _ = writeln!(out, "http_cache_requests_total{{result=\"miss\"}} {}", misses);
+
+        header(&mut out, "http_cache_hit_ratio", "gauge", "Share of conditional GET requests answered with 304, 0 before the first one");
+
+        let ratio = if hits + misses == 0 { 0.0 } else { hits as f64 / (hits + misses) as f64 };
+
+        let _ = writeln!(out, "http_cache_hit_ratio {}", ratio);
+
+        header(&mut out, "nutrition_requests_total", "counter", "Calls to the nutrition API by outcome: ok, unknown_dish or unavailable");
+
+        let calls = self.nutrition_calls.lock().unwrap();
+        let mut errors = 0;
+
+        for outcome in ["ok", "unknown_dish", "unavailable"] {
+            let count = calls.get(outcome).copied().unwrap_or(0);
+
+            if outcome == "unavailable" {
+                errors += count;
+            }
+
+            let _ = writeln!(out, "nutrition_requests_total{{outcome=\"{}\"}} {}", outcome, count);
+        }
+
+        drop(calls);
+
+        header(&mut out, "nutrition_request_errors_total", "counter", "Calls to the nutrition API that failed or couldn't be read");
+
+        let _ = writeln!(out, "nutrition_request_errors_total {}", errors);
+
+        header(&mut out, "nutrition_request_duration_seconds", "histogram", "Time taken by calls to the nutrition API");
+
+        self.nutrition_latency.lock().unwrap().render(&mut out, "nutrition_request_duration_seconds", "");
+
+        header(&mut out, "stored_dishes", "gauge", "Dishes currently stored, across all tenants");
+
+        let _ = writeln!(out, "stored_dishes {}", dishes);
+
+        header(&mut out, "stored_meals", "gauge", "Meals currently stored, across all tenants");
+
+        let _ = writeln!(out, "stored_meals {}", meals);
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn request_labels(method: &str, route: &str, status: u16) -> String {
+    format!("method=\"{}\",route=\"{}\",status=\"{}\"", escape(method), escape(route), status)
+}
+
+fn braced(labels: &str) -> String {
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels)
+    }
+}
+
+// Label values may not contain raw backslashes, quotes or newlines
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
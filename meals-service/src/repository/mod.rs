@@ -1,2 +1,15 @@
 pub mod state;
-pub mod nutrition_api_client;
\ No newline at end of file
+pub mod nutrition_api_client;
+pub mod names;
+pub mod search;
+pub mod listing;
+pub mod events;
+pub mod webhooks;
+pub mod webhook_client;
+pub mod api_keys;
+pub mod jwt;
+pub mod tenants;
+pub mod metrics;
+pub mod health;
+pub mod in_flight;
+pub mod circuit_breaker;
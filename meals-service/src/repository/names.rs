@@ -0,0 +1,34 @@
+use caseless::default_case_fold_str;
+
+use unicode_normalization::UnicodeNormalization;
+
+// Canonical key used for uniqueness checks and lookups, so "Pasta", "pasta" and " pasta " all
+// resolve to the same dish while the name stored on the dish stays as entered
+pub fn normalize_name(name: &str) -> String {
+    let composed: String = name.nfc().collect();
+    let folded: String = default_case_fold_str(&composed).nfc().collect();
+
+    folded.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_name;
+
+    #[test]
+    fn folds_case_and_whitespace() {
+        assert_eq!(normalize_name("Pasta"), "pasta");
+        assert_eq!(normalize_name("  Chicken \t Soup "), "chicken soup");
+    }
+
+    #[test]
+    fn folds_beyond_lowercase() {
+        assert_eq!(normalize_name("STRASSE"), normalize_name("straße"));
+    }
+
+    #[test]
+    fn composes_accents() {
+        // "e" followed by a combining acute accent, and the precomposed "é"
+        assert_eq!(normalize_name("Cre\u{301}pe"), normalize_name("Cr\u{e9}pe"));
+    }
+}
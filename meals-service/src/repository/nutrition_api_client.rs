@@ -1,5 +1,26 @@
+use std::time::{Duration, Instant};
+
 use serde::Deserialize;
 
+use utoipa::ToSchema;
+
+use async_graphql::Enum;
+
+use crate::{logging::request_id, telemetry};
+
+const NUTRITION_API_URL: &str = "https://api.api-ninjas.com/v1/nutrition";
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, ToSchema, Enum)]
+#[serde(rename_all = "lowercase")]
+pub enum MultiItemPolicy {
+    #[default]
+    Sum,
+    Reject,
+    Confirm
+}
+
 #[derive(Deserialize, Debug)]
 pub struct NutritionInformation {
     name: String,
@@ -13,7 +34,9 @@ pub struct NutritionInformation {
     cholesterol_mg: i32,
     carbohydrates_total_g: f32,
     fiber_g: f32,
-    sugar_g: f32
+    sugar_g: f32,
+    #[serde(skip)]
+    items: Vec<NutritionInformation>
 }
 
 impl Default for NutritionInformation {
@@ -30,7 +53,8 @@ impl Default for NutritionInformation {
             cholesterol_mg: 0,
             carbohydrates_total_g: 0.0,
             fiber_g: 0.0,
-            sugar_g: 0.0    
+            sugar_g: 0.0,
+            items: Vec::new()
         }
     }
 }
@@ -51,14 +75,58 @@ impl NutritionInformation {
     pub fn get_sugar(&self) -> f32 {
         self.sugar_g
     }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_items(&self) -> &Vec<NutritionInformation> {
+        &self.items
+    }
+
+    // Sum adds up every matched item, while the dish still lists each of them as it always does.
+    // Reject refuses queries matching more than one item and Confirm only accepts them when the
+    // caller listed exactly the item names the API matched
+    pub fn check_items(&self, policy: &MultiItemPolicy, confirmed: &[String]) -> Result<(), i32> {
+        if self.items.len() <= 1 {
+            return Ok(())
+        }
+
+        match policy {
+            MultiItemPolicy::Sum => Ok(()),
+            MultiItemPolicy::Reject => Err(-7),
+            MultiItemPolicy::Confirm => {
+                let mut matched: Vec<String> = self.items.iter().map(|item| item.name.to_lowercase()).collect();
+                let mut expected: Vec<String> = confirmed.iter().map(|name| name.trim().to_lowercase()).collect();
+
+                matched.sort();
+                expected.sort();
+
+                if matched == expected {
+                    Ok(())
+                } else {
+                    Err(-7)
+                }
+            }
+        }
+    }
 }
 
 pub async fn get_nutrition_data(dish_name: String) -> Result<NutritionInformation, i32> {
-    let request_url = format!("https://api.api-ninjas.com/v1/nutrition?query={dish_name}", dish_name = dish_name);
+    let request_url = format!("{url}?query={dish_name}", url = NUTRITION_API_URL, dish_name = dish_name);
 
-    let request = reqwest::Client::new()
+    let mut request = reqwest::Client::new()
         .get(&request_url)
         .header("X-Api-Key", "EpQWFI0Orj6GvxKtl4Xl0w==73SfSrj86CiQT2NK");
+
+    // Lets the calls made for a request be found in the provider's logs and traces
+    if let Some(id) = request_id() {
+        request = request.header("X-Request-Id", id);
+    }
+
+    if let Some(context) = telemetry::current() {
+        request = request.header("traceparent", context.traceparent());
+    }
     
     let dishes: Vec<NutritionInformation>;
 
@@ -80,6 +148,12 @@ pub async fn get_nutrition_data(dish_name: String) -> Result<NutritionInformatio
         return Err(-3)
     }
 
+    Ok(combine(dish_name, dishes))
+}
+
+// One record named after the query with the nutrients of every matched item added together,
+// keeping the items themselves, even when there is only one
+fn combine(dish_name: String, dishes: Vec<NutritionInformation>) -> NutritionInformation {
     let mut data = NutritionInformation { name: dish_name, ..Default::default() };
 
     for dish in dishes {
@@ -94,7 +168,92 @@ pub async fn get_nutrition_data(dish_name: String) -> Result<NutritionInformatio
         data.carbohydrates_total_g += dish.carbohydrates_total_g;
         data.fiber_g += dish.fiber_g;
         data.sugar_g += dish.sugar_g;
+
+        data.items.push(dish);
+    }
+
+    data
+}
+
+// Checks that the nutrition API can be reached, returning how long it took to answer. The request
+// has no query or key, so it doesn't count against the quota, and any HTTP status means the API is up
+pub async fn probe() -> Result<Duration, String> {
+    let started = Instant::now();
+
+    let client = reqwest::Client::builder()
+        .timeout(PROBE_TIMEOUT)
+        .build()
+        .map_err(|err| err.to_string())?;
+
+    client.get(NUTRITION_API_URL).send().await.map_err(|err| err.to_string())?;
+
+    Ok(started.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{combine, MultiItemPolicy, NutritionInformation};
+
+    fn item(name: &str, calories: f32) -> NutritionInformation {
+        serde_json::from_value(json!({
+            "name": name,
+            "calories": calories,
+            "serving_size_g": 100.0,
+            "fat_total_g": 1.0,
+            "fat_saturated_g": 0.5,
+            "protein_g": 2.0,
+            "sodium_mg": 10.0,
+            "potassium_mg": 20,
+            "cholesterol_mg": 0,
+            "carbohydrates_total_g": 30.0,
+            "fiber_g": 1.0,
+            "sugar_g": 3.0
+        })).unwrap()
+    }
+
+    #[test]
+    fn single_item_matches_keep_their_item() {
+        let data = combine(String::from("pasta"), vec![item("pasta", 150.0)]);
+
+        assert_eq!(data.get_name(), "pasta");
+        assert_eq!(data.get_calories(), 150.0);
+        assert_eq!(data.get_items().len(), 1);
+        assert_eq!(data.get_items()[0].get_name(), "pasta");
+    }
+
+    #[test]
+    fn multi_item_matches_are_summed_and_keep_every_item() {
+        let data = combine(String::from("chicken and rice"), vec![item("chicken", 200.0), item("rice", 130.0)]);
+
+        assert_eq!(data.get_name(), "chicken and rice");
+        assert_eq!(data.get_calories(), 330.0);
+        assert_eq!(data.get_size(), 200.0);
+        assert_eq!(data.get_sugar(), 6.0);
+
+        let names: Vec<&String> = data.get_items().iter().map(|item| item.get_name()).collect();
+
+        assert_eq!(names, ["chicken", "rice"]);
     }
 
-    Ok(data)
-}
\ No newline at end of file
+    #[test]
+    fn single_item_matches_pass_every_policy() {
+        let data = combine(String::from("pasta"), vec![item("pasta", 150.0)]);
+
+        assert_eq!(data.check_items(&MultiItemPolicy::Sum, &Vec::new()), Ok(()));
+        assert_eq!(data.check_items(&MultiItemPolicy::Reject, &Vec::new()), Ok(()));
+        assert_eq!(data.check_items(&MultiItemPolicy::Confirm, &Vec::new()), Ok(()));
+    }
+
+    #[test]
+    fn multi_item_matches_follow_the_policy() {
+        let data = combine(String::from("chicken and rice"), vec![item("chicken", 200.0), item("rice", 130.0)]);
+
+        assert_eq!(data.check_items(&MultiItemPolicy::Sum, &[]), Ok(()));
+        assert_eq!(data.check_items(&MultiItemPolicy::Reject, &[]), Err(-7));
+        assert_eq!(data.check_items(&MultiItemPolicy::Confirm, &[String::from(" Rice"), String::from("chicken")]), Ok(()));
+        assert_eq!(data.check_items(&MultiItemPolicy::Confirm, &[String::from("chicken")]), Err(-7));
+        assert_eq!(data.check_items(&MultiItemPolicy::Confirm, &[]), Err(-7));
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::{Serialize, Deserialize};
+
+use strsim::levenshtein;
+
+use utoipa::{
+    ToSchema,
+    PartialSchema,
+    openapi::{RefOr, Ref, Schema, AllOfBuilder, ObjectBuilder, Type}
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchType {
+    Exact,
+    Prefix,
+    Substring,
+    Fuzzy
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResult<T> {
+    score: f32,
+    #[serde(rename = "match")]
+    match_type: MatchType,
+    #[serde(flatten)]
+    item: T
+}
+
+impl<T> SearchResult<T> {
+    pub fn new(hit: &SearchHit, item: T) -> SearchResult<T> {
+        SearchResult {
+            score: hit.score,
+            match_type: hit.match_type,
+            item
+        }
+    }
+}
+
+// Written by hand because the derive can't describe the flattened generic item
+impl<T: ToSchema> PartialSchema for SearchResult<T> {
+    fn schema() -> RefOr<Schema> {
+        let ranking = ObjectBuilder::new()
+            .property("score", ObjectBuilder::new().schema_type(Type::Number).description(Some("Relevance between 0 and 1")))
+            .required("score")
+            .property("match", Ref::from_schema_name(MatchType::name()))
+            .required("match");
+
+        AllOfBuilder::new()
+            .item(Ref::from_schema_name(T::name()))
+            .item(ranking)
+            .into()
+    }
+}
+
+impl<T: ToSchema> ToSchema for SearchResult<T> {}
+
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub id: i32,
+    pub score: f32,
+    pub match_type: MatchType
+}
+
+// Keys are the normalized names also used in dish_ids/meal_ids. Full names are kept ordered so
+// prefix matches are a range scan, and every word of a name is indexed so "soup" finds "chicken soup"
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchIndex {
+    names: BTreeMap<String, i32>,
+    words: BTreeMap<String, BTreeSet<i32>>
+}
+
+impl SearchIndex {
+    pub fn new() -> SearchIndex {
+        SearchIndex::default()
+    }
+
+    pub fn insert(&mut self, key: &str, id: i32) {
+        self.names.insert(key.to_string(), id);
+
+        for word in key.split(' ') {
+            self.words.entry(word.to_string()).or_default().insert(id);
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        if let Some(id) = self.names.remove(key) {
+            for word in key.split(' ') {
+                if let Some(ids) = self.words.get_mut(word) {
+                    ids.remove(&id);
+
+                    if ids.is_empty() {
+                        self.words.remove(word);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let mut hits: HashMap<i32, SearchHit> = HashMap::new();
+
+        if query.is_empty() {
+            return Vec::new()
+        }
+
+        let mut record = |id: i32, score: f32, match_type: MatchType| {
+            let hit = hits.entry(id).or_insert(SearchHit { id, score, match_type });
+
+            if score > hit.score {
+                hit.score = score;
+                hit.match_type = match_type;
+            }
+        };
+
+        for (name, id) in self.names.range(query.to_string()..) {
+            if !name.starts_with(query) {
+                break
+            }
+
+            if name == query {
+                record(*id, 1.0, MatchType::Exact);
+            } else {
+                record(*id, 0.9 - 0.1 * ratio(query, name), MatchType::Prefix);
+            }
+        }
+
+        for (word, ids) in self.words.range(query.to_string()..) {
+            if !word.starts_with(query) {
+                break
+            }
+
+            for id in ids {
+                record(*id, 0.7 - 0.1 * ratio(query, word), MatchType::Prefix);
+            }
+        }
+
+        // Allow one typo for short queries and roughly one per four characters after that
+        let max_distance = std::cmp::max(1, query.chars().count() / 4);
+
+        for (name, id) in self.names.iter() {
+            if name.contains(query) {
+                record(*id, 0.5 - 0.1 * ratio(query, name), MatchType::Substring);
+                continue
+            }
+
+            let distance = std::iter::once(name.as_str())
+                .chain(name.split(' '))
+                .map(|candidate| levenshtein(query, candidate))
+                .min()
+                .unwrap_or(usize::MAX);
+
+            if distance <= max_distance {
+                record(*id, 0.4 - 0.3 * (distance as f32 / (max_distance + 1) as f32), MatchType::Fuzzy);
+            }
+        }
+
+        let mut ranked: Vec<SearchHit> = hits.into_values().collect();
+
+        ranked.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+        ranked.truncate(limit);
+
+        ranked
+    }
+}
+
+// Share of the candidate not covered by the query, so tighter matches rank first
+fn ratio(query: &str, candidate: &str) -> f32 {
+    let candidate_len = candidate.chars().count() as f32;
+
+    (candidate_len - query.chars().count() as f32) / candidate_len
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::names::normalize_name;
+
+    use super::{SearchIndex, MatchType};
+
+    fn index() -> SearchIndex {
+        let mut index = SearchIndex::new();
+
+        for (id, name) in [(1, "Pasta"), (2, "Chicken Soup"), (3, "Pasta Salad")] {
+            index.insert(&normalize_name(name), id);
+        }
+
+        index
+    }
+
+    fn search(index: &SearchIndex, query: &str) -> Vec<(i32, MatchType)> {
+        index.search(&normalize_name(query), 10).into_iter().map(|hit| (hit.id, hit.match_type)).collect()
+    }
+
+    #[test]
+    fn ranks_exact_matches_first() {
+        assert_eq!(search(&index(), " PASTA "), vec![(1, MatchType::Exact), (3, MatchType::Prefix)]);
+    }
+
+    #[test]
+    fn finds_words_substrings_and_typos() {
+        assert_eq!(search(&index(), "soup"), vec![(2, MatchType::Prefix)]);
+        assert_eq!(search(&index(), "hick"), vec![(2, MatchType::Substring)]);
+        assert_eq!(search(&index(), "Chiken"), vec![(2, MatchType::Fuzzy)]);
+    }
+
+    #[test]
+    fn forgets_removed_names() {
+        let mut index = index();
+
+        index.remove(&normalize_name("Chicken Soup"));
+
+        assert!(search(&index, "soup").is_empty());
+    }
+}
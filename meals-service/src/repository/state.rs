@@ -1,22 +1,83 @@
 use std::{
-    sync::Mutex,
-    collections::HashMap, i32
+    sync::{Arc, Mutex},
+    collections::HashMap, i32,
+    time::Instant
 };
 
 use serde::{Serialize, Deserialize};
 
-use crate::{repository::nutrition_api_client::get_nutrition_data, api::meal};
+use serde_json::json;
 
-use super::nutrition_api_client::NutritionInformation;
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::{
+    repository::nutrition_api_client::get_nutrition_data,
+    telemetry::{Span, Kind}
+};
+
+use super::{
+    nutrition_api_client::{NutritionInformation, MultiItemPolicy},
+    names::normalize_name,
+    search::{SearchIndex, SearchResult},
+    events::{EventLog, Resource, Action},
+    webhooks::WebhookRegistry,
+    api_keys::ApiKeyRegistry,
+    jwt::JwtVerifier,
+    metrics::Metrics,
+    health::{Readiness, NutritionApiProbe},
+    in_flight::InFlight,
+    circuit_breaker::CircuitBreaker,
+    tenants::{Quota, TenantSummary},
+    listing::{self, Field, Listable, ListOptions}
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DishItem {
+    name: String,
+    cal: f32,
+    size: f32,
+    sodium: f32,
+    sugar: f32
+}
+
+impl DishItem {
+    fn new(data: &NutritionInformation) -> DishItem {
+        DishItem {
+            name: data.get_name().clone(),
+            cal: data.get_calories(),
+            size: data.get_size(),
+            sodium: data.get_sodium(),
+            sugar: data.get_sugar()
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn nutrient(&self, field: Field) -> Option<f32> {
+        match field {
+            Field::Cal => Some(self.cal),
+            Field::Size => Some(self.size),
+            Field::Sodium => Some(self.sodium),
+            Field::Sugar => Some(self.sugar),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Dish {
     ID: i32,
     name: String,
     cal: f32,
     size: f32,
     sodium: f32,
-    sugar: f32
+    sugar: f32,
+    /// Every item the nutrition API matched, a single one for most names
+    items: Vec<DishItem>,
+    #[serde(skip)]
+    version: u64
 }
 
 impl Dish {
@@ -27,12 +88,43 @@ impl Dish {
             cal: data.get_calories(),
             size: data.get_size(),
             sodium: data.get_sodium(),
-            sugar: data.get_sugar()
+            sugar: data.get_sugar(),
+            items: data.get_items().iter().map(DishItem::new).collect(),
+            version: 0
         }
     }
+
+    // Revision of the dish collection at which this dish last changed, used for ETags
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn items(&self) -> &Vec<DishItem> {
+        &self.items
+    }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+impl Listable for Dish {
+    fn id(&self) -> i32 {
+        self.ID
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn nutrient(&self, field: Field) -> Option<f32> {
+        match field {
+            Field::Cal => Some(self.cal),
+            Field::Size => Some(self.size),
+            Field::Sodium => Some(self.sodium),
+            Field::Sugar => Some(self.sugar),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
 pub struct Meal {
     ID: i32,
     name: String,
@@ -41,7 +133,9 @@ pub struct Meal {
     dessert: Option<i32>,
     cal: f32,
     sodium: f32,
-    sugar: f32
+    sugar: f32,
+    #[serde(skip)]
+    version: u64
 }
 
 impl Meal {
@@ -54,30 +148,250 @@ impl Meal {
             dessert: Some(dessert.ID),
             cal: appetizer.cal + main.cal + dessert.cal,
             sodium: appetizer.sodium + main.sodium + dessert.sodium,
-            sugar: appetizer.sugar + main.sugar + dessert.sugar
+            sugar: appetizer.sugar + main.sugar + dessert.sugar,
+            version: 0
         }
     }
+
+    // Revision of the meal collection at which this meal last changed, used for ETags
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    // IDs of the appetizer, main and dessert, missing where the dish was unknown
+    pub fn courses(&self) -> [Option<i32>; 3] {
+        [self.appetizer, self.main, self.dessert]
+    }
+
+    // Applies a change in the nutrition values of a dish to every course of the meal that uses it.
+    // Returns false when the meal doesn't contain the dish
+    fn replace_dish(&mut self, old: &Dish, new: &Dish) -> bool {
+        let courses = [self.appetizer, self.main, self.dessert].iter().filter(|course| **course == Some(old.ID)).count() as f32;
+
+        if courses == 0.0 {
+            return false
+        }
+
+        self.cal += courses * (new.cal - old.cal);
+        self.sodium += courses * (new.sodium - old.sodium);
+        self.sugar += courses * (new.sugar - old.sugar);
+
+        true
+    }
 }
 
+impl Listable for Meal {
+    fn id(&self) -> i32 {
+        self.ID
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Meals don't track serving size, so size filters never match them
+    fn nutrient(&self, field: Field) -> Option<f32> {
+        match field {
+            Field::Cal => Some(self.cal),
+            Field::Sodium => Some(self.sodium),
+            Field::Sugar => Some(self.sugar),
+            _ => None
+        }
+    }
+}
+
+// Dishes and meals of one tenant. Names, IDs, versions and quotas are all separate per tenant,
+// so teams sharing a deployment don't see or collide with each other's dishes
 #[derive(Serialize, Deserialize)]
-pub struct AppState {
+pub struct Tenant {
+    name: String,
+    quota: Mutex<Quota>,
     dish_counter: Mutex<i32>,
     dishes: Mutex<HashMap<i32, Dish>>,
     dish_ids: Mutex<HashMap<String, i32>>,
+    dish_index: Mutex<SearchIndex>,
+    dish_revision: Mutex<u64>,
     meal_counter: Mutex<i32>,
     meals: Mutex<HashMap<i32, Meal>>,
-    meal_ids: Mutex<HashMap<String, i32>>
+    meal_ids: Mutex<HashMap<String, i32>>,
+    meal_index: Mutex<SearchIndex>,
+    meal_revision: Mutex<u64>,
+    #[serde(skip)]
+    events: Arc<EventLog>,
+    #[serde(skip)]
+    metrics: Arc<Metrics>,
+    #[serde(skip)]
+    in_flight: Arc<InFlight>,
+    #[serde(skip)]
+    breaker: Arc<CircuitBreaker>
+}
+
+// Everything shared by the HTTP and gRPC servers: the tenants, created as they are first used, and
+// the events, webhooks and credentials common to all of them
+pub struct AppState {
+    tenants: Mutex<HashMap<String, Arc<Tenant>>>,
+    default_quota: Mutex<Quota>,
+    events: Arc<EventLog>,
+    metrics: Arc<Metrics>,
+    in_flight: Arc<InFlight>,
+    breaker: Arc<CircuitBreaker>,
+    readiness: Mutex<Readiness>,
+    nutrition_api_probe: NutritionApiProbe,
+    auth_required: Mutex<bool>,
+    webhooks: WebhookRegistry,
+    api_keys: ApiKeyRegistry,
+    jwt: JwtVerifier
 }
 
 impl AppState {
     pub fn new() -> AppState {
         return AppState {
+            tenants: Mutex::new(HashMap::new()),
+            default_quota: Mutex::new(Quota::default()),
+            events: Arc::new(EventLog::default()),
+            metrics: Arc::new(Metrics::default()),
+            in_flight: Arc::new(InFlight::default()),
+            breaker: Arc::new(CircuitBreaker::default()),
+            readiness: Mutex::new(Readiness::Ready),
+            nutrition_api_probe: NutritionApiProbe::default(),
+            auth_required: Mutex::new(false),
+            webhooks: WebhookRegistry::default(),
+            api_keys: ApiKeyRegistry::default(),
+            jwt: JwtVerifier::default()
+        }
+    }
+
+    // Adds a tenant with no dishes or meals and the default quota, unless there already is one by
+    // that name. Returns the tenant and whether it was added. Names are expected to have been
+    // validated with tenants::is_valid_name
+    pub fn create_tenant(&self, name: &str) -> (Arc<Tenant>, bool) {
+        let mut tenants = self.tenants.lock().unwrap();
+
+        if let Some(tenant) = tenants.get(name) {
+            return (tenant.clone(), false)
+        }
+
+        let tenant = Arc::new(Tenant::new(name, *self.default_quota.lock().unwrap(), self.events.clone(), self.metrics.clone(), self.in_flight.clone(), self.breaker.clone()));
+
+        tenants.insert(name.to_string(), tenant.clone());
+
+        (tenant, true)
+    }
+
+    // Requests only ever look tenants up, so naming one that doesn't exist (-5) adds nothing
+    pub fn get_tenant(&self, name: &str) -> Result<Arc<Tenant>, i32> {
+        self.tenants.lock().unwrap().get(name).cloned().ok_or(-5)
+    }
+
+    pub fn get_tenants(&self) -> Vec<TenantSummary> {
+        let mut tenants: Vec<TenantSummary> = self.tenants.lock().unwrap().values().map(|tenant| tenant.summary()).collect();
+
+        tenants.sort_by(|a, b| a.name.cmp(&b.name));
+
+        tenants
+    }
+
+    // Quota given to tenants created from now on. Existing tenants keep theirs
+    pub fn set_default_quota(&self, quota: Quota) {
+        *self.default_quota.lock().unwrap() = quota;
+    }
+
+    pub fn events(&self) -> &EventLog {
+        &self.events
+    }
+
+    // Whether requests need an API key or JWT. Decided once at startup, so revoking keys can't turn
+    // authentication off
+    pub fn is_auth_required(&self) -> bool {
+        *self.auth_required.lock().unwrap()
+    }
+
+    pub fn set_auth_required(&self, required: bool) {
+        *self.auth_required.lock().unwrap() = required;
+    }
+
+    pub fn nutrition_api_probe(&self) -> &NutritionApiProbe {
+        &self.nutrition_api_probe
+    }
+
+    pub fn get_readiness(&self) -> Readiness {
+        *self.readiness.lock().unwrap()
+    }
+
+    pub fn set_readiness(&self, readiness: Readiness) {
+        *self.readiness.lock().unwrap() = readiness;
+    }
+
+    pub fn in_flight(&self) -> &InFlight {
+        &self.in_flight
+    }
+
+    pub fn nutrition_api_breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn webhooks(&self) -> &WebhookRegistry {
+        &self.webhooks
+    }
+
+    pub fn api_keys(&self) -> &ApiKeyRegistry {
+        &self.api_keys
+    }
+
+    pub fn jwt(&self) -> &JwtVerifier {
+        &self.jwt
+    }
+}
+
+impl Tenant {
+    fn new(name: &str, quota: Quota, events: Arc<EventLog>, metrics: Arc<Metrics>, in_flight: Arc<InFlight>, breaker: Arc<CircuitBreaker>) -> Tenant {
+        return Tenant {
+            name: name.to_string(),
+            quota: Mutex::new(quota),
             dish_counter: Mutex::new(0),
             dishes: Mutex::new(HashMap::new()),
             dish_ids: Mutex::new(HashMap::new()),
+            dish_index: Mutex::new(SearchIndex::new()),
+            dish_revision: Mutex::new(0),
             meal_counter: Mutex::new(0),
             meals: Mutex::new(HashMap::new()),
-            meal_ids: Mutex::new(HashMap::new())
+            meal_ids: Mutex::new(HashMap::new()),
+            meal_index: Mutex::new(SearchIndex::new()),
+            meal_revision: Mutex::new(0),
+            events,
+            metrics,
+            in_flight,
+            breaker
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_quota(&self) -> Quota {
+        *self.quota.lock().unwrap()
+    }
+
+    pub fn set_quota(&self, quota: Quota) {
+        *self.quota.lock().unwrap() = quota;
+    }
+
+    pub fn summary(&self) -> TenantSummary {
+        // Counted one at a time, as updates lock meals before dishes and holding both here in the
+        // other order could deadlock with them
+        let dishes = self.dishes.lock().unwrap().len();
+        let meals = self.meals.lock().unwrap().len();
+
+        TenantSummary {
+            name: self.name.clone(),
+            dishes,
+            meals,
+            quota: self.get_quota()
         }
     }
 
@@ -85,13 +399,17 @@ impl AppState {
         let mut counter = self.dish_counter.lock().unwrap();
         *counter = 3;
 
+        *self.dish_revision.lock().unwrap() = 1;
+
         let pasta = Dish {
             ID: 1,
             name: String::from("pasta"),
             cal: 500.0,
             size: 150.0,
             sodium: 12.0,
-            sugar: 1.0
+            sugar: 1.0,
+            items: vec![DishItem { name: String::from("pasta"), cal: 500.0, size: 150.0, sodium: 12.0, sugar: 1.0 }],
+            version: 1
         };
 
         let focaccia = Dish {
@@ -100,7 +418,9 @@ impl AppState {
             cal: 251.4,
             size: 100.0,
             sodium: 570.0,
-            sugar: 1.8
+            sugar: 1.8,
+            items: vec![DishItem { name: String::from("focaccia"), cal: 251.4, size: 100.0, sodium: 570.0, sugar: 1.8 }],
+            version: 1
         };
 
         let chicken_soup = Dish {
@@ -109,7 +429,9 @@ impl AppState {
             cal: 33.2,
             size: 100.0,
             sodium: 230.0,
-            sugar: 1.0
+            sugar: 1.0,
+            items: vec![DishItem { name: String::from("chicken soup"), cal: 33.2, size: 100.0, sodium: 230.0, sugar: 1.0 }],
+            version: 1
         };
         
         let salad = Dish {
@@ -118,27 +440,53 @@ impl AppState {
             cal: 28.2,
             size: 100.0,
             sodium: 78.2,
-            sugar: 6.0
+            sugar: 6.0,
+            items: vec![DishItem { name: String::from("salad"), cal: 28.2, size: 100.0, sodium: 78.2, sugar: 6.0 }],
+            version: 1
         };
 
         let mut dishes = self.dishes.lock().unwrap();
         let mut dish_ids = self.dish_ids.lock().unwrap();
+        let mut dish_index = self.dish_index.lock().unwrap();
         
         dishes.insert(1, pasta);
         dish_ids.insert(String::from("pasta"), 1);
+        dish_index.insert("pasta", 1);
         
         dishes.insert(2, focaccia);
         dish_ids.insert(String::from("focaccia"), 2);
+        dish_index.insert("focaccia", 2);
         
         dishes.insert(3, chicken_soup);
         dish_ids.insert(String::from("chicken soup"), 3);
+        dish_index.insert("chicken soup", 3);
         
         dishes.insert(4, salad);
         dish_ids.insert(String::from("salad"), 4);
+        dish_index.insert("salad", 4);
 
         4
     }
 
+    // Revisions grow with every change to a collection, so a dish or meal stamped with the current
+    // revision can never share a version with an earlier state of itself
+    fn next_revision(&self, revision: &Mutex<u64>) -> u64 {
+        let mut rev = revision.lock().unwrap();
+        *rev += 1;
+
+        *rev
+    }
+
+    // Listings read the version of their collection before the collection itself, so a change made
+    // in between can only make their ETag look stale rather than fresh
+    pub fn get_dishes_version(&self) -> u64 {
+        *self.dish_revision.lock().unwrap()
+    }
+
+    pub fn get_meals_version(&self) -> u64 {
+        *self.meal_revision.lock().unwrap()
+    }
+
     fn increment_counter(&self, counter: &Mutex<i32>) -> i32 {
         let mut ctr = counter.lock().unwrap();
         *ctr += 1;
@@ -148,46 +496,115 @@ impl AppState {
         return val
     }
 
-    pub async fn create_dish(&self, name: String) -> Result<i32, i32> {
-        let dish = self.get_dish_by_name(name.clone());
+    // Span of one operation on the tenant's dishes or meals, e.g. AppState.create_dish
+    fn span(&self, operation: &str) -> Span {
+        let mut span = Span::start(format!("AppState.{}", operation), Kind::Internal);
 
-        match dish {
-            Ok(_) => Err(-2), // Dish already exists
-            _ => {
-                let nutrition_data = get_nutrition_data(name.clone()).await;
+        span.set_attribute("tenant", self.name.clone());
 
-                match nutrition_data {
-                    Ok(data) => {
-                        let dish_id = self.increment_counter(&self.dish_counter);
+        span
+    }
 
-                        let dish = Dish::new(dish_id, name.clone(), data);
+    // Queries the nutrition API, counting the call in the metrics and tracing it as a child of the
+    // operation's span. Shutdowns wait for the call to finish. Fails with -4 without calling the API
+    // while the circuit breaker is open
+    async fn get_nutrition_data(&self, name: String) -> Result<NutritionInformation, i32> {
+        if !self.breaker.allow() {
+            return Err(-4)
+        }
 
-                        let mut dishes = self.dishes.lock().unwrap();
-                        let mut dish_ids = self.dish_ids.lock().unwrap();
-                        
-                        dishes.insert(dish_id, dish);
-                        dish_ids.insert(name.clone(), dish_id);
+        let _call = self.in_flight.begin();
+        let started = Instant::now();
 
-                        Ok(dish_id)
-                    },
-                    Err(err) => Err(err),
-                }
-            },
+        let mut span = Span::start("GET nutrition", Kind::Client);
+
+        span.set_attribute("dish.name", name.clone());
+
+        let nutrition_data = span.scope(get_nutrition_data(name)).await;
+
+        match nutrition_data {
+            Err(-3) => span.set_attribute("error.code", -3),
+            Err(code) => span.set_error(format!("error code {}", code)),
+            Ok(_) => {}
         }
+
+        self.metrics.record_nutrition_call(&nutrition_data, started.elapsed());
+
+        // Names the API doesn't recognize (-3) still mean it answered
+        self.breaker.record(!matches!(nutrition_data, Err(-4)));
+
+        nutrition_data
+    }
+
+    pub async fn create_dish(&self, name: String, policy: &MultiItemPolicy, confirmed_items: &[String]) -> Result<i32, i32> {
+        let span = self.span("create_dish");
+
+        span.scope(async {
+            let dish = self.get_dish_by_name(name.clone());
+
+            if !Quota::allows(self.get_quota().dishes, self.dishes.lock().unwrap().len()) {
+                return Err(-14)
+            }
+
+            match dish {
+                Ok(_) => Err(-2), // Dish already exists
+                _ => {
+                    let nutrition_data = self.get_nutrition_data(name.clone()).await;
+
+                    match nutrition_data {
+                        Ok(data) => {
+                            data.check_items(policy, confirmed_items)?;
+
+                            let mut dishes = self.dishes.lock().unwrap();
+                            let mut dish_ids = self.dish_ids.lock().unwrap();
+
+                            let key = normalize_name(&name);
+
+                            // Checked again now that the locks are held, since another request for the
+                            // same name may have finished while the nutrition API was being queried
+                            if dish_ids.contains_key(&key) {
+                                return Err(-2)
+                            }
+
+                            if !Quota::allows(self.get_quota().dishes, dishes.len()) {
+                                return Err(-14)
+                            }
+
+                            let dish_id = self.increment_counter(&self.dish_counter);
+
+                            let mut dish = Dish::new(dish_id, name.clone(), data);
+
+                            dish.version = self.next_revision(&self.dish_revision);
+
+                            self.events.publish(&self.name, Resource::Dish, Action::Created, &dish);
+
+                            dishes.insert(dish_id, dish);
+                            dish_ids.insert(key.clone(), dish_id);
+                            self.dish_index.lock().unwrap().insert(&key, dish_id);
+
+                            Ok(dish_id)
+                        },
+                        Err(err) => Err(err),
+                    }
+                },
+            }
+        }).await
     }
 
     pub fn get_dish_by_name(&self, name: String) -> Result<Dish, i32> {
-        let dish_ids = self.dish_ids.lock().unwrap();
+        let _span = self.span("get_dish_by_name");
 
-        let dish_id = dish_ids.get(&name);
+        let dish_id = self.dish_ids.lock().unwrap().get(&normalize_name(&name)).copied();
 
         match dish_id {
-            Some(id) => return self.get_dish_by_id(*id),
+            Some(id) => self.get_dish_by_id(id),
             None => Err(-5)
         }
     }
 
     pub fn get_dish_by_id(&self, id: i32) -> Result<Dish, i32> {
+        let _span = self.span("get_dish_by_id");
+
         let dishes = self.dishes.lock().unwrap();
 
         let dish = dishes.get(&id);
@@ -198,22 +615,33 @@ impl AppState {
         }
     }
 
-    pub fn get_dishes(&self) -> HashMap<i32, Dish> {
+    pub fn get_dishes(&self, options: &ListOptions) -> (usize, Vec<Dish>) {
+        let _span = self.span("get_dishes");
+
         let dishes = self.dishes.lock().unwrap();
 
-        let dishes_copy = dishes.clone();
+        let (total, page) = listing::select(dishes.values(), options);
 
-        return dishes_copy
+        (total, page.into_iter().cloned().collect())
     }
 
     pub fn delete_dish_by_id(&self, id: i32) -> Result<i32, i32> {
+        let _span = self.span("delete_dish_by_id");
+
         let mut dishes = self.dishes.lock().unwrap();
 
         match dishes.remove(&id) {
             Some(data) => {
                 let mut dish_ids = self.dish_ids.lock().unwrap();
 
-                match dish_ids.remove(&data.name) {
+                self.next_revision(&self.dish_revision);
+                self.events.publish(&self.name, Resource::Dish, Action::Deleted, &json!({ "ID": id }));
+
+                let key = normalize_name(&data.name);
+
+                self.dish_index.lock().unwrap().remove(&key);
+
+                match dish_ids.remove(&key) {
                     Some(_) => return Ok(id),
                     None => panic!("Dish not found")
                 }
@@ -223,22 +651,101 @@ impl AppState {
     }
 
     pub fn delete_dish_by_name(&self, name: String) -> Result<i32, i32> {
-        let mut dish_ids = self.dish_ids.lock().unwrap();
+        let _span = self.span("delete_dish_by_name");
 
-        match dish_ids.remove(&name) {
-            Some(id) => {
-                let mut dishes = self.dishes.lock().unwrap();
+        let dish_id = self.dish_ids.lock().unwrap().get(&normalize_name(&name)).copied();
 
-                match dishes.remove(&id) {
-                    Some(_) => return Ok(id),
-                    None => panic!("Dish not found")
-                }
-            },
+        match dish_id {
+            Some(id) => self.delete_dish_by_id(id),
             None => Err(-5)
         }
     }
 
+    // Looks the dish up again under a possibly new name while keeping its ID, and carries the change
+    // in nutrition values over to the meals that contain it. `precondition` works as in update_meal
+    pub async fn update_dish(&self, id: i32, name: String, policy: &MultiItemPolicy, confirmed_items: &[String], precondition: impl Fn(u64) -> bool) -> Result<i32, i32> {
+        let span = self.span("update_dish");
+
+        span.scope(async {
+            let current = self.get_dish_by_id(id)?;
+
+            if !precondition(current.version) {
+                return Err(-9)
+            }
+
+            let old_key = normalize_name(&current.name);
+            let new_key = normalize_name(&name);
+
+            if new_key != old_key && self.dish_ids.lock().unwrap().contains_key(&new_key) {
+                return Err(-2)
+            }
+
+            let data = self.get_nutrition_data(name.clone()).await?;
+
+            data.check_items(policy, confirmed_items)?;
+
+            let mut meals = self.meals.lock().unwrap();
+            let mut dishes = self.dishes.lock().unwrap();
+            let mut dish_ids = self.dish_ids.lock().unwrap();
+
+            // Checked again now that the locks are held, in case the dish changed while the nutrition
+            // API was being queried
+            let old = match dishes.get(&id) {
+                Some(dish) if dish.version != current.version => return Err(-9),
+                Some(dish) => dish.clone(),
+                None => return Err(-5)
+            };
+
+            if new_key != old_key {
+                if dish_ids.contains_key(&new_key) {
+                    return Err(-2)
+                }
+
+                let mut dish_index = self.dish_index.lock().unwrap();
+
+                dish_ids.remove(&old_key);
+                dish_index.remove(&old_key);
+
+                dish_ids.insert(new_key.clone(), id);
+                dish_index.insert(&new_key, id);
+            }
+
+            let mut dish = Dish::new(id, name, data);
+
+            dish.version = self.next_revision(&self.dish_revision);
+
+            let mut meal_version: Option<u64> = None;
+
+            self.events.publish(&self.name, Resource::Dish, Action::Updated, &dish);
+
+            for meal in meals.values_mut() {
+                if meal.replace_dish(&old, &dish) {
+                    meal.version = *meal_version.get_or_insert_with(|| self.next_revision(&self.meal_revision));
+
+                    self.events.publish(&self.name, Resource::Meal, Action::Updated, meal);
+                }
+            }
+
+            dishes.insert(id, dish);
+
+            Ok(id)
+        }).await
+    }
+
+    pub fn search_dishes(&self, query: &str, limit: usize) -> Vec<SearchResult<Dish>> {
+        let _span = self.span("search_dishes");
+
+        let hits = self.dish_index.lock().unwrap().search(&normalize_name(query), limit);
+        let dishes = self.dishes.lock().unwrap();
+
+        hits.iter()
+            .filter_map(|hit| dishes.get(&hit.id).map(|dish| SearchResult::new(hit, dish.clone())))
+            .collect()
+    }
+
     pub fn create_meal(&self, name: String, appetizer_id: &i32, main_id: &i32, dessert_id: &i32) -> Result<i32, i32> {
+        let _span = self.span("create_meal");
+
         let appetizer: Dish;
         let main: Dish;
         let dessert: Dish;
@@ -265,31 +772,50 @@ impl AppState {
             Err(_) => return Err(-6)
         }
 
+        let mut meals = self.meals.lock().unwrap();
+        let mut meal_ids = self.meal_ids.lock().unwrap();
+
+        let key = normalize_name(&name);
+
+        // Checked again now that the locks are held, since another request for the same name may
+        // have created the meal after the check above
+        if meal_ids.contains_key(&key) {
+            return Err(-2)
+        }
+
+        if !Quota::allows(self.get_quota().meals, meals.len()) {
+            return Err(-14)
+        }
+
         let meal_id = self.increment_counter(&self.meal_counter);
 
-        let meal = Meal::new(&meal_id, name.clone(), &appetizer, &main, &dessert);
+        let mut meal = Meal::new(&meal_id, name.clone(), &appetizer, &main, &dessert);
 
-        let mut meals = self.meals.lock().unwrap();
-        let mut meal_ids = self.meal_ids.lock().unwrap();
+        meal.version = self.next_revision(&self.meal_revision);
+
+        self.events.publish(&self.name, Resource::Meal, Action::Created, &meal);
 
         meals.insert(meal_id, meal);
-        meal_ids.insert(name, meal_id);
+        meal_ids.insert(key.clone(), meal_id);
+        self.meal_index.lock().unwrap().insert(&key, meal_id);
 
         Ok(meal_id)
     }
 
-    pub fn get_meal_by_name(&self, name: &String) -> Result<Meal, i32> {
-        let meal_ids = self.meal_ids.lock().unwrap();
+    pub fn get_meal_by_name(&self, name: &str) -> Result<Meal, i32> {
+        let _span = self.span("get_meal_by_name");
 
-        let meal_id = meal_ids.get(name);
+        let meal_id = self.meal_ids.lock().unwrap().get(&normalize_name(name)).copied();
 
         match meal_id {
-            Some(id) => self.get_meal_by_id(*id),
+            Some(id) => self.get_meal_by_id(id),
             None => Err(-5)
         }
     }
 
     pub fn get_meal_by_id(&self, id: i32) -> Result<Meal, i32> {
+        let _span = self.span("get_meal_by_id");
+
         let meals = self.meals.lock().unwrap();
 
         let meal = meals.get(&id);
@@ -300,22 +826,44 @@ impl AppState {
         }
     }
 
-    pub fn get_meals(&self) -> HashMap<i32, Meal> {
+    pub fn get_meals(&self, options: &ListOptions) -> (usize, Vec<Meal>) {
+        let _span = self.span("get_meals");
+
         let meals = self.meals.lock().unwrap();
 
-        let meals_copy = meals.clone();
+        let (total, page) = listing::select(meals.values(), options);
+
+        (total, page.into_iter().cloned().collect())
+    }
+
+    pub fn search_meals(&self, query: &str, limit: usize) -> Vec<SearchResult<Meal>> {
+        let _span = self.span("search_meals");
 
-        return meals_copy
+        let hits = self.meal_index.lock().unwrap().search(&normalize_name(query), limit);
+        let meals = self.meals.lock().unwrap();
+
+        hits.iter()
+            .filter_map(|hit| meals.get(&hit.id).map(|meal| SearchResult::new(hit, meal.clone())))
+            .collect()
     }
 
     pub fn delete_meal_by_id(&self, id: &i32) -> Result<i32, i32> {
+        let _span = self.span("delete_meal_by_id");
+
         let mut meals = self.meals.lock().unwrap();
 
         match meals.remove(id) {
             Some(meal) => {
                 let mut meal_ids = self.meal_ids.lock().unwrap();
 
-                match meal_ids.remove(&meal.name) {
+                self.next_revision(&self.meal_revision);
+                self.events.publish(&self.name, Resource::Meal, Action::Deleted, &json!({ "ID": id }));
+
+                let key = normalize_name(&meal.name);
+
+                self.meal_index.lock().unwrap().remove(&key);
+
+                match meal_ids.remove(&key) {
                     Some(_) => Ok(*id),
                     None => panic!("Meal not found")
                 }
@@ -324,47 +872,30 @@ impl AppState {
         }
     }
 
-    pub fn delete_meal_by_name(&self, name: &String) -> Result<i32, i32> {
-        let mut meal_ids = self.meal_ids.lock().unwrap();
-        
-        match meal_ids.remove(name) {
-            Some(id) => {
-                let mut meals = self.meals.lock().unwrap();
+    pub fn delete_meal_by_name(&self, name: &str) -> Result<i32, i32> {
+        let _span = self.span("delete_meal_by_name");
 
-                match meals.remove(&id) {
-                    Some(_) => Ok(id),
-                    None => panic!("Meal not found")
-                }
-            },
+        let meal_id = self.meal_ids.lock().unwrap().get(&normalize_name(name)).copied();
+
+        match meal_id {
+            Some(id) => self.delete_meal_by_id(&id),
             None => Err(-5)
         }
     }
 
-    pub fn update_meal(&self, id: &i32, name: &String, appetizer_id: &i32, main_id: &i32, dessert_id: &i32) -> Result<i32, i32> {
+    // `precondition` is given the current version of the meal and refuses the update (-9) by
+    // returning false, which lets callers implement If-Match without racing other updates
+    pub fn update_meal(&self, id: &i32, name: &str, appetizer_id: &i32, main_id: &i32, dessert_id: &i32, precondition: impl Fn(u64) -> bool) -> Result<i32, i32> {
+        let _span = self.span("update_meal");
+
         let mut meals = self.meals.lock().unwrap();
 
         let meal = meals.get(id);
 
         match meal {
             Some(data) => {
-                if *name != data.name {
-                    let new_meal_id = self.get_meal_by_name(name);
-
-                    match new_meal_id {
-                        Ok(_) => return Err(-2),
-                        _ => {}
-                    }
-
-                    let mut meal_ids = self.meal_ids.lock().unwrap();
-
-                    let res = meal_ids.remove(&data.name);
-
-                    match res {
-                        Some(id) => {
-                            meal_ids.insert(name.clone(), id);
-                        },
-                        None => panic!("Meal not found")
-                    }
+                if !precondition(data.version) {
+                    return Err(-9)
                 }
 
                 let appetizer: Dish;
@@ -386,7 +917,36 @@ impl AppState {
                     Err(_) => return Err(-6)
                 }
 
-                let new_meal = Meal::new(id, name.clone(), &appetizer, &main, &dessert);
+                let old_key = normalize_name(&data.name);
+                let new_key = normalize_name(name);
+
+                if new_key != old_key {
+                    let mut meal_ids = self.meal_ids.lock().unwrap();
+
+                    if meal_ids.contains_key(&new_key) {
+                        return Err(-2)
+                    }
+
+                    let res = meal_ids.remove(&old_key);
+
+                    match res {
+                        Some(id) => {
+                            let mut meal_index = self.meal_index.lock().unwrap();
+
+                            meal_index.remove(&old_key);
+                            meal_index.insert(&new_key, id);
+
+                            meal_ids.insert(new_key, id);
+                        },
+                        None => panic!("Meal not found")
+                    }
+                }
+
+                let mut new_meal = Meal::new(id, name.to_string(), &appetizer, &main, &dessert);
+
+                new_meal.version = self.next_revision(&self.meal_revision);
+
+                self.events.publish(&self.name, Resource::Meal, Action::Updated, &new_meal);
 
                 meals.insert(*id, new_meal);
 
@@ -0,0 +1,48 @@
+use serde::{Serialize, Deserialize};
+
+use utoipa::ToSchema;
+
+// Tenant of requests that don't name one, so single-team deployments never have to
+pub const DEFAULT_TENANT: &str = "default";
+
+const MAX_NAME_LENGTH: usize = 64;
+
+// Tenant names are used in headers, claims and URLs, so they are kept to lowercase letters,
+// digits, '-' and '_'
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+// Most dishes and meals a tenant may have at once. Missing limits are unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Quota {
+    pub dishes: Option<usize>,
+    pub meals: Option<usize>
+}
+
+impl Quota {
+    // Reads the limits every new tenant starts with from TENANT_MAX_DISHES and TENANT_MAX_MEALS
+    pub fn from_env() -> Quota {
+        let limit = |name: &str| std::env::var(name).ok().and_then(|limit| limit.parse::<usize>().ok());
+
+        Quota {
+            dishes: limit("TENANT_MAX_DISHES"),
+            meals: limit("TENANT_MAX_MEALS")
+        }
+    }
+
+    pub fn allows(limit: Option<usize>, count: usize) -> bool {
+        limit.is_none_or(|limit| count < limit)
+    }
+}
+
+// Admin view of one tenant
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TenantSummary {
+    pub name: String,
+    pub dishes: usize,
+    pub meals: usize,
+    pub quota: Quota
+}
@@ -0,0 +1,174 @@
+use std::{
+    sync::Arc,
+    time::Duration
+};
+
+use hmac::{Hmac, Mac};
+
+use serde_json::json;
+
+use sha2::Sha256;
+
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle};
+
+use super::{
+    state::AppState,
+    events::Event,
+    webhooks::{Subscription, Delivery, now}
+};
+
+const MAX_ATTEMPTS: u32 = 5;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Delay before the second attempt, doubled for every attempt after that. WEBHOOK_BACKOFF_MS
+// overrides it, e.g. to retry quickly while testing against a local receiver
+const DEFAULT_BACKOFF: Duration = Duration::from_secs(1);
+
+// Forwards every change published by AppState to the subscribed webhooks. Runs until the event
+// log is closed on shutdown, and then waits for the deliveries and retries still pending
+pub async fn dispatch(state: Arc<AppState>) {
+    let backoff = backoff();
+    let client = client();
+
+    let (_, mut receiver) = state.events().subscribe(None);
+    let mut last_id: u64 = 0;
+    let mut deliveries: Vec<JoinHandle<()>> = Vec::new();
+
+    loop {
+        deliveries.retain(|delivery| !delivery.is_finished());
+
+        let event = match receiver.recv().await {
+            Ok(event) => event,
+            // Catch up from the event buffer rather than skipping the events missed
+            Err(RecvError::Lagged(_)) => {
+                let (missed, resubscribed) = state.events().subscribe(Some(last_id));
+
+                receiver = resubscribed;
+
+                if !missed.complete {
+                    log::warn!("Webhook deliveries fell behind, events after {} were dropped from the buffer before being sent", last_id);
+                }
+
+                for event in missed.events {
+                    last_id = event.id;
+
+                    deliveries.extend(send(&state, &client, backoff, event));
+                }
+
+                continue
+            },
+            Err(RecvError::Closed) => break
+        };
+
+        last_id = event.id;
+
+        deliveries.extend(send(&state, &client, backoff, event));
+    }
+
+    for delivery in deliveries {
+        let _ = delivery.await;
+    }
+}
+
+// Sends a ping event to one subscription only, so receivers can be tested without changing any
+// dish or meal
+pub fn ping(state: Arc<AppState>, subscription: Subscription) {
+    let payload = format!("{{\"id\":0,\"event\":\"ping\",\"tenant\":{},\"data\":{{\"ID\":{}}}}}", json!(subscription.tenant()), subscription.id());
+
+    actix_web::rt::spawn(deliver(state, client(), backoff(), subscription, 0, String::from("ping"), payload));
+}
+
+fn backoff() -> Duration {
+    std::env::var("WEBHOOK_BACKOFF_MS")
+        .ok()
+        .and_then(|millis| millis.parse::<u64>().ok())
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_BACKOFF)
+}
+
+fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+fn send(state: &Arc<AppState>, client: &reqwest::Client, backoff: Duration, event: Event) -> Vec<JoinHandle<()>> {
+    let name = event.name();
+    let payload = format!("{{\"id\":{},\"event\":\"{}\",\"tenant\":{},\"data\":{}}}", event.id, name, json!(event.tenant), event.data);
+
+    state.webhooks().get_recipients(&event.tenant, &name)
+        .into_iter()
+        .map(|subscription| actix_web::rt::spawn(deliver(state.clone(), client.clone(), backoff, subscription, event.id, name.clone(), payload.clone())))
+        .collect()
+}
+
+// Sends the payload until the receiver answers with a 2xx status, waiting longer after every
+// failure, and gives up after MAX_ATTEMPTS or once the subscription is removed
+async fn deliver(state: Arc<AppState>, client: reqwest::Client, backoff: Duration, subscription: Subscription, event_id: u64, event: String, payload: String) {
+    let mut delay = backoff;
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let timestamp = now();
+
+        let result = client
+            .post(subscription.url())
+            .header("Content-Type", "application/json")
+            .header("X-Webhook-Id", subscription.id().to_string())
+            .header("X-Webhook-Event", event.as_str())
+            .header("X-Webhook-Delivery", event_id.to_string())
+            .header("X-Webhook-Timestamp", timestamp.to_string())
+            .header("X-Webhook-Signature", sign(subscription.secret(), timestamp, &payload))
+            .body(payload.clone())
+            .send()
+            .await
+            .map(|response| response.status().as_u16())
+            .map_err(|err| err.to_string());
+
+        let delivery = Delivery::new(subscription.id(), event_id, &event, attempt, result);
+        let delivered = delivery.delivered();
+
+        state.webhooks().record(delivery);
+
+        if delivered || attempt == MAX_ATTEMPTS {
+            return
+        }
+
+        tokio::time::sleep(delay).await;
+
+        delay *= 2;
+
+        if !state.webhooks().is_subscribed(subscription.id()) {
+            return
+        }
+    }
+}
+
+// HMAC-SHA256 of "<timestamp>.<body>", so receivers can reject both forged and replayed requests
+pub fn sign(secret: &str, timestamp: u64, payload: &str) -> String {
+    let mut mac = match Hmac::<Sha256>::new_from_slice(secret.as_bytes()) {
+        Ok(mac) => mac,
+        Err(_) => return String::new()
+    };
+
+    mac.update(format!("{}.{}", timestamp, payload).as_bytes());
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sign;
+
+    // Computed independently with Python's hmac module
+    #[test]
+    fn signs_the_timestamp_and_body() {
+        assert_eq!(sign("whsec_test", 1700000000, "{\"id\":1}"), "sha256=2f441ba4b3b2d50d28a9ab9d9fd8880376ecd1eb5d0435401553f5d8d0a5dcf8");
+    }
+
+    #[test]
+    fn depends_on_the_timestamp() {
+        assert_ne!(sign("whsec_test", 1700000000, "{\"id\":1}"), sign("whsec_test", 1700000001, "{\"id\":1}"));
+    }
+}
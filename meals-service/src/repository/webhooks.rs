@@ -0,0 +1,208 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH}
+};
+
+use rand::RngCore;
+
+use serde::Serialize;
+
+use utoipa::ToSchema;
+
+// Entries kept in the delivery log across all subscriptions. The oldest are dropped first
+const DELIVERY_LOG_SIZE: usize = 1000;
+
+// Event names a subscription may ask for, besides "*" for all of them and "dish.*" or "meal.*"
+// for every change to one kind of resource
+pub const EVENT_NAMES: [&str; 6] = ["dish.created", "dish.updated", "dish.deleted", "meal.created", "meal.updated", "meal.deleted"];
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Subscription {
+    #[serde(rename = "ID")]
+    id: i32,
+    // Tenant whose changes are sent, the one the subscription was created for
+    tenant: String,
+    url: String,
+    events: Vec<String>,
+    // Only returned when the subscription is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
+    created: u64
+}
+
+impl Subscription {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn tenant(&self) -> &str {
+        &self.tenant
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn secret(&self) -> &str {
+        self.secret.as_deref().unwrap_or_default()
+    }
+
+    pub fn wants(&self, event: &str) -> bool {
+        self.events.iter().any(|pattern| match pattern.strip_suffix(".*") {
+            Some(resource) => event.split('.').next() == Some(resource),
+            None => pattern == "*" || pattern == event
+        })
+    }
+
+    // Copy to show to clients, leaving out the secret
+    fn redacted(&self) -> Subscription {
+        Subscription { secret: None, ..self.clone() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Delivery {
+    subscription: i32,
+    // ID of the event, as sent in the payload and by GET /events
+    event_id: u64,
+    event: String,
+    attempt: u32,
+    // Status code returned by the receiver, missing when it couldn't be reached
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    delivered: bool,
+    timestamp: u64
+}
+
+impl Delivery {
+    pub fn new(subscription: i32, event_id: u64, event: &str, attempt: u32, result: Result<u16, String>) -> Delivery {
+        Delivery {
+            subscription,
+            event_id,
+            event: event.to_string(),
+            attempt,
+            status: result.as_ref().ok().copied(),
+            delivered: matches!(result, Ok(200..=299)),
+            error: result.err(),
+            timestamp: now()
+        }
+    }
+
+    pub fn delivered(&self) -> bool {
+        self.delivered
+    }
+}
+
+#[derive(Default)]
+pub struct WebhookRegistry {
+    counter: Mutex<i32>,
+    subscriptions: Mutex<HashMap<i32, Subscription>>,
+    deliveries: Mutex<VecDeque<Delivery>>
+}
+
+impl WebhookRegistry {
+    // Validates the URL and event patterns (-1) and generates a secret when none is given
+    pub fn subscribe(&self, tenant: &str, url: String, events: Vec<String>, secret: Option<String>) -> Result<Subscription, i32> {
+        match reqwest::Url::parse(&url) {
+            Ok(parsed) if parsed.scheme() == "http" || parsed.scheme() == "https" => {},
+            _ => return Err(-1)
+        }
+
+        let events = match events.is_empty() {
+            true => vec![String::from("*")],
+            false => events
+        };
+
+        let known = |pattern: &String| pattern == "*" || pattern == "dish.*" || pattern == "meal.*" || EVENT_NAMES.contains(&pattern.as_str());
+
+        if !events.iter().all(known) {
+            return Err(-1)
+        }
+
+        let secret = match secret {
+            Some(secret) if secret.is_empty() => return Err(-1),
+            Some(secret) => secret,
+            None => {
+                let mut bytes = [0u8; 32];
+
+                rand::thread_rng().fill_bytes(&mut bytes);
+
+                hex::encode(bytes)
+            }
+        };
+
+        let mut counter = self.counter.lock().unwrap();
+        *counter += 1;
+
+        let subscription = Subscription {
+            id: *counter,
+            tenant: tenant.to_string(),
+            url,
+            events,
+            secret: Some(secret),
+            created: now()
+        };
+
+        self.subscriptions.lock().unwrap().insert(subscription.id, subscription.clone());
+
+        Ok(subscription)
+    }
+
+    pub fn get_subscriptions(&self) -> Vec<Subscription> {
+        let mut subscriptions: Vec<Subscription> = self.subscriptions.lock().unwrap().values().map(Subscription::redacted).collect();
+
+        subscriptions.sort_by_key(|subscription| subscription.id);
+
+        subscriptions
+    }
+
+    pub fn get_subscription(&self, id: i32) -> Result<Subscription, i32> {
+        self.subscriptions.lock().unwrap().get(&id).map(Subscription::redacted).ok_or(-5)
+    }
+
+    // Subscriptions of the tenant with their secrets, for signing deliveries
+    pub fn get_recipients(&self, tenant: &str, event: &str) -> Vec<Subscription> {
+        self.subscriptions.lock().unwrap()
+            .values()
+            .filter(|subscription| subscription.tenant == tenant && subscription.wants(event))
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_recipient(&self, id: i32) -> Result<Subscription, i32> {
+        self.subscriptions.lock().unwrap().get(&id).cloned().ok_or(-5)
+    }
+
+    pub fn unsubscribe(&self, id: i32) -> Result<i32, i32> {
+        match self.subscriptions.lock().unwrap().remove(&id) {
+            Some(_) => Ok(id),
+            None => Err(-5)
+        }
+    }
+
+    pub fn is_subscribed(&self, id: i32) -> bool {
+        self.subscriptions.lock().unwrap().contains_key(&id)
+    }
+
+    pub fn record(&self, delivery: Delivery) {
+        let mut deliveries = self.deliveries.lock().unwrap();
+
+        if deliveries.len() == DELIVERY_LOG_SIZE {
+            deliveries.pop_front();
+        }
+
+        deliveries.push_back(delivery);
+    }
+
+    // Delivery attempts for a subscription, most recent first
+    pub fn get_deliveries(&self, id: i32) -> Vec<Delivery> {
+        self.deliveries.lock().unwrap().iter().rev().filter(|delivery| delivery.subscription == id).cloned().collect()
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or_default()
+}
@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+const DEFAULT_DRAIN_DELAY: Duration = Duration::from_secs(5);
+
+// Time given to the requests in progress once a shutdown begins, after which the process exits
+// anyway. SHUTDOWN_TIMEOUT overrides it, in seconds
+pub fn grace_period() -> Duration {
+    seconds("SHUTDOWN_TIMEOUT").unwrap_or(DEFAULT_GRACE_PERIOD)
+}
+
+// Part of the grace period spent still accepting requests while reporting draining, so load
+// balancers polling /health/ready stop sending new ones before the listeners close.
+// SHUTDOWN_DELAY overrides it, in seconds
+pub fn drain_delay() -> Duration {
+    seconds("SHUTDOWN_DELAY").unwrap_or(DEFAULT_DRAIN_DELAY).min(grace_period())
+}
+
+fn seconds(name: &str) -> Option<Duration> {
+    std::env::var(name)
+        .ok()
+        .and_then(|seconds| seconds.parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+// Resolves with the name of the first SIGTERM, sent by docker stop and Kubernetes, or SIGINT
+// (Ctrl+C) received
+pub async fn signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use futures::future::{select, Either};
+        use tokio::signal::unix::{signal, SignalKind};
+
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            return match select(Box::pin(terminate.recv()), Box::pin(tokio::signal::ctrl_c())).await {
+                Either::Left(_) => "SIGTERM",
+                Either::Right(_) => "SIGINT"
+            }
+        }
+    }
+
+    let _ = tokio::signal::ctrl_c().await;
+
+    "SIGINT"
+}
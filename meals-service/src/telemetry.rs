@@ -0,0 +1,574 @@
+use std::{
+    future::Future,
+    io::Write,
+    sync::OnceLock,
+    time::{Duration, SystemTime, UNIX_EPOCH}
+};
+
+use serde_json::{json, Value};
+
+use tokio::sync::{
+    mpsc::{self, Receiver, Sender},
+    oneshot
+};
+
+// Spans waiting to be sent to the collector. Spans ending while it is full are dropped rather than
+// slowing requests down
+const QUEUE_SIZE: usize = 4096;
+
+const BATCH_SIZE: usize = 512;
+
+const EXPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+// Longest a shutdown waits for the last spans to be sent
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+const DEFAULT_ENDPOINT: &str = "http://localhost:4318";
+
+const DEFAULT_SERVICE_NAME: &str = "meals-service";
+
+tokio::task_local! {
+    // Span that spans started by the running future are children of
+    static CURRENT: SpanContext;
+}
+
+static EXPORTER: OnceLock<Exporter> = OnceLock::new();
+
+// Spans are encoded and sent here rather than with the opentelemetry-otlp crate. Only traces are
+// exported, as OTLP/JSON, which takes little code, while the SDK would keep a context of its own
+// next to CURRENT above and needs reqwest 0.12 next to the 0.11 used for the nutrition API
+enum Exporter {
+    // One OTLP/JSON document per line on stdout
+    Console { service: String },
+    // Sent in batches to an OTLP/HTTP collector by a background task
+    Otlp { queue: Sender<Message> }
+}
+
+enum Message {
+    Span(Value),
+    // Send what is waiting now and answer once done
+    Flush(oneshot::Sender<()>)
+}
+
+// Kinds as numbered by OTLP
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Internal = 1,
+    Server = 2,
+    Client = 3
+}
+
+// Identifies a span within its trace, as carried by the W3C traceparent header
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpanContext {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    sampled: bool
+}
+
+impl SpanContext {
+    // "00-<trace ID>-<parent ID>-<flags>". Headers of later versions may have more fields after
+    // these, which are ignored
+    pub fn from_traceparent(header: &str) -> Option<SpanContext> {
+        let parts: Vec<&str> = header.trim().split('-').collect();
+
+        if parts.len() < 4 || parts[0].len() != 2 || parts[0] == "ff" || (parts[0] == "00" && parts.len() != 4) {
+            return None
+        }
+
+        let trace_id: [u8; 16] = hex::decode(parts[1]).ok()?.try_into().ok()?;
+        let span_id: [u8; 8] = hex::decode(parts[2]).ok()?.try_into().ok()?;
+        let flags: [u8; 1] = hex::decode(parts[3]).ok()?.try_into().ok()?;
+
+        if trace_id == [0; 16] || span_id == [0; 8] {
+            return None
+        }
+
+        Some(SpanContext { trace_id, span_id, sampled: flags[0] & 1 == 1 })
+    }
+
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-{:02x}", hex::encode(self.trace_id), hex::encode(self.span_id), self.sampled as u8)
+    }
+
+    pub fn trace_id(&self) -> String {
+        hex::encode(self.trace_id)
+    }
+}
+
+// Timed operation, exported when dropped. Spans started inside a scope of another span, or of a
+// remote parent, belong to the same trace
+pub struct Span {
+    name: String,
+    kind: Kind,
+    context: SpanContext,
+    parent: Option<[u8; 8]>,
+    start: SystemTime,
+    attributes: Vec<(&'static str, Value)>,
+    error: Option<String>
+}
+
+impl Span {
+    // Child of the current span, or the first span of a new trace
+    pub fn start(name: impl Into<String>, kind: Kind) -> Span {
+        Span::child_of(name, kind, current())
+    }
+
+    pub fn child_of(name: impl Into<String>, kind: Kind, parent: Option<SpanContext>) -> Span {
+        let context = SpanContext {
+            trace_id: parent.map(|parent| parent.trace_id).unwrap_or_else(rand::random),
+            span_id: rand::random(),
+            sampled: parent.map(|parent| parent.sampled).unwrap_or(true)
+        };
+
+        Span {
+            name: name.into(),
+            kind,
+            context,
+            parent: parent.map(|parent| parent.span_id),
+            start: SystemTime::now(),
+            attributes: Vec::new(),
+            error: None
+        }
+    }
+
+    pub fn set_attribute(&mut self, key: &'static str, value: impl Into<Value>) {
+        self.attributes.push((key, value.into()));
+    }
+
+    pub fn set_error(&mut self, message: impl Into<String>) {
+        self.error = Some(message.into());
+    }
+
+    // Runs the future with this span as the parent of the spans it starts
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        CURRENT.scope(self.context, future).await
+    }
+
+    fn to_otlp(&self, end: SystemTime) -> Value {
+        let attributes: Vec<Value> = self.attributes.iter().map(|(key, value)| attribute(key, value)).collect();
+
+        let status = match &self.error {
+            Some(message) => json!({"code": 2, "message": message}),
+            None => json!({"code": 0})
+        };
+
+        json!({
+            "traceId": hex::encode(self.context.trace_id),
+            "spanId": hex::encode(self.context.span_id),
+            "parentSpanId": self.parent.map(hex::encode).unwrap_or_default(),
+            "name": self.name,
+            "kind": self.kind as u8,
+            "startTimeUnixNano": nanos(self.start),
+            "endTimeUnixNano": nanos(end),
+            "attributes": attributes,
+            "status": status
+        })
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if !self.context.sampled {
+            return
+        }
+
+        let exporter = match EXPORTER.get() {
+            Some(exporter) => exporter,
+            None => return
+        };
+
+        let span = self.to_otlp(SystemTime::now());
+
+        match exporter {
+            Exporter::Console { service } => {
+                let _ = writeln!(std::io::stdout().lock(), "{}", document(service, vec![span]));
+            },
+            Exporter::Otlp { queue } => {
+                let _ = queue.try_send(Message::Span(span));
+            }
+        }
+    }
+}
+
+// Current span of the running future, if any
+pub fn current() -> Option<SpanContext> {
+    CURRENT.try_with(|context| *context).ok()
+}
+
+// Reads OTEL_TRACES_EXPORTER: "otlp" to send spans to the collector at OTEL_EXPORTER_OTLP_ENDPOINT
+// over HTTP, "console" to print them to stdout and "none", the default, to drop them. Spans are still
+// created and propagated when they aren't exported. Has to be called from within the runtime
+pub fn init() -> Result<(), String> {
+    let service = std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| String::from(DEFAULT_SERVICE_NAME));
+
+    let exporter = match std::env::var("OTEL_TRACES_EXPORTER").as_deref() {
+        Err(_) | Ok("none") => return Ok(()),
+        Ok("console") => Exporter::Console { service },
+        Ok("otlp") => {
+            let endpoint = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").unwrap_or_else(|_| String::from(DEFAULT_ENDPOINT));
+            let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+
+            let (queue, receiver) = mpsc::channel(QUEUE_SIZE);
+
+            actix_web::rt::spawn(export(url, service, receiver));
+
+            Exporter::Otlp { queue }
+        },
+        Ok(other) => return Err(format!("unknown exporter {}", other))
+    };
+
+    let _ = EXPORTER.set(exporter);
+
+    Ok(())
+}
+
+// Sends the spans still waiting, for shutdowns
+pub async fn flush() {
+    match EXPORTER.get() {
+        Some(Exporter::Otlp { queue }) => {
+            let (done, flushed) = oneshot::channel();
+
+            if queue.send(Message::Flush(done)).await.is_ok() {
+                let _ = tokio::time::timeout(FLUSH_TIMEOUT, flushed).await;
+            }
+        },
+        Some(Exporter::Console { .. }) => {
+            let _ = std::io::stdout().flush();
+        },
+        None => {}
+    }
+}
+
+// Sends the queued spans every EXPORT_INTERVAL, as soon as a batch is full or when flushed
+async fn export(url: String, service: String, mut receiver: Receiver<Message>) {
+    let client = reqwest::Client::new();
+    let mut batch = Vec::new();
+    let mut deadline = tokio::time::Instant::now() + EXPORT_INTERVAL;
+
+    loop {
+        let mut flushed = None;
+
+        let closed = match tokio::time::timeout_at(deadline, receiver.recv()).await {
+            Ok(Some(Message::Flush(done))) => {
+                flushed = Some(done);
+
+                false
+            },
+            Ok(Some(Message::Span(span))) => {
+                batch.push(span);
+
+                if batch.len() < BATCH_SIZE {
+                    continue
+                }
+
+                false
+            },
+            Ok(None) => true,
+            Err(_) => false
+        };
+
+        deadline = tokio::time::Instant::now() + EXPORT_INTERVAL;
+
+        if !batch.is_empty() {
+            let result = client
+                .post(&url)
+                .json(&document(&service, std::mem::take(&mut batch)))
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+
+            if let Err(err) = result {
+                log::warn!("Couldn't export spans to {}: {}", url, err);
+            }
+        }
+
+        if let Some(done) = flushed {
+            let _ = done.send(());
+        }
+
+        if closed {
+            return
+        }
+    }
+}
+
+fn document(service: &str, spans: Vec<Value>) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {"attributes": [attribute("service.name", &Value::from(service))]},
+            "scopeSpans": [{
+                "scope": {"name": DEFAULT_SERVICE_NAME},
+                "spans": spans
+            }]
+        }]
+    })
+}
+
+// OTLP/JSON writes 64-bit integers as strings
+fn attribute(key: &str, value: &Value) -> Value {
+    let value = match value {
+        Value::Bool(value) => json!({"boolValue": value}),
+        Value::Number(value) if value.is_i64() || value.is_u64() => json!({"intValue": value.to_string()}),
+        Value::Number(value) => json!({"doubleValue": value}),
+        Value::String(value) => json!({"stringValue": value}),
+        other => json!({"stringValue": other.to_string()})
+    };
+
+    json!({"key": key, "value": value})
+}
+
+fn nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        time::{Duration, UNIX_EPOCH}
+    };
+
+    use serde_json::{json, Value};
+
+    use tokio::{
+        sync::{mpsc, oneshot},
+        time::timeout
+    };
+
+    use super::{attribute, current, document, export, Kind, Message, Span, SpanContext, BATCH_SIZE};
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn traceparent_is_parsed_and_written_back() {
+        let context = SpanContext::from_traceparent(TRACEPARENT).unwrap();
+
+        assert_eq!(context.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert!(context.sampled);
+        assert_eq!(context.traceparent(), TRACEPARENT);
+
+        let unsampled = SpanContext::from_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00").unwrap();
+
+        assert!(!unsampled.sampled);
+    }
+
+    #[test]
+    fn later_versions_may_have_more_fields() {
+        assert!(SpanContext::from_traceparent("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra").is_some());
+    }
+
+    #[test]
+    fn invalid_traceparents_are_ignored() {
+        let invalid = [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e47-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-1",
+            "00-4bf92f3577b34da6a3ce929d0e0e473g-00f067aa0ba902b7-01"
+        ];
+
+        for header in invalid {
+            assert_eq!(SpanContext::from_traceparent(header), None, "{}", header);
+        }
+    }
+
+    #[test]
+    fn children_continue_the_trace_of_their_parent() {
+        let parent = SpanContext::from_traceparent(TRACEPARENT).unwrap();
+
+        let span = Span::child_of("GET /dishes", Kind::Server, Some(parent));
+
+        assert_eq!(span.context.trace_id, parent.trace_id);
+        assert_ne!(span.context.span_id, parent.span_id);
+        assert_eq!(span.parent, Some(parent.span_id));
+        assert!(span.context.sampled);
+
+        let root = Span::child_of("GET /dishes", Kind::Server, None);
+
+        assert_eq!(root.parent, None);
+        assert_ne!(root.context.trace_id, [0; 16]);
+    }
+
+    #[actix_web::test]
+    async fn spans_started_in_a_scope_are_children_of_its_span() {
+        let parent = Span::start("AppState.create_dish", Kind::Internal);
+
+        assert_eq!(current(), None);
+
+        let child = parent.scope(async { Span::start("AppState.get_dish_by_name", Kind::Internal) }).await;
+
+        assert_eq!(child.context.trace_id, parent.context.trace_id);
+        assert_eq!(child.parent, Some(parent.context.span_id));
+    }
+
+    #[test]
+    fn spans_are_encoded_as_otlp_json() {
+        let parent = SpanContext::from_traceparent(TRACEPARENT).unwrap();
+
+        let mut span = Span::child_of("GET nutrition", Kind::Client, Some(parent));
+
+        span.start = UNIX_EPOCH + Duration::from_nanos(1_000);
+        span.set_attribute("dish.name", "pasta");
+        span.set_error("error code -4");
+
+        let otlp = span.to_otlp(UNIX_EPOCH + Duration::from_nanos(2_500));
+
+        assert_eq!(otlp["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(otlp["parentSpanId"], "00f067aa0ba902b7");
+        assert_eq!(otlp["spanId"], hex::encode(span.context.span_id));
+        assert_eq!(otlp["name"], "GET nutrition");
+        assert_eq!(otlp["kind"], 3);
+        assert_eq!(otlp["startTimeUnixNano"], "1000");
+        assert_eq!(otlp["endTimeUnixNano"], "2500");
+        assert_eq!(otlp["attributes"], json!([{"key": "dish.name", "value": {"stringValue": "pasta"}}]));
+        assert_eq!(otlp["status"], json!({"code": 2, "message": "error code -4"}));
+    }
+
+    #[test]
+    fn root_spans_have_an_empty_parent_and_an_unset_status() {
+        let otlp = Span::child_of("GET /", Kind::Server, None).to_otlp(UNIX_EPOCH);
+
+        assert_eq!(otlp["parentSpanId"], "");
+        assert_eq!(otlp["status"], json!({"code": 0}));
+    }
+
+    #[test]
+    fn attributes_use_the_otlp_value_types() {
+        assert_eq!(attribute("a", &Value::from(true)), json!({"key": "a", "value": {"boolValue": true}}));
+        assert_eq!(attribute("a", &Value::from(-3)), json!({"key": "a", "value": {"intValue": "-3"}}));
+        assert_eq!(attribute("a", &Value::from(u64::MAX)), json!({"key": "a", "value": {"intValue": "18446744073709551615"}}));
+        assert_eq!(attribute("a", &Value::from(0.5)), json!({"key": "a", "value": {"doubleValue": 0.5}}));
+        assert_eq!(attribute("a", &Value::from("b")), json!({"key": "a", "value": {"stringValue": "b"}}));
+        assert_eq!(attribute("a", &json!([1])), json!({"key": "a", "value": {"stringValue": "[1]"}}));
+    }
+
+    #[test]
+    fn documents_name_the_service() {
+        let document = document("meals", vec![json!({"name": "GET /"})]);
+
+        assert_eq!(document["resourceSpans"][0]["resource"]["attributes"], json!([{"key": "service.name", "value": {"stringValue": "meals"}}]));
+        assert_eq!(document["resourceSpans"][0]["scopeSpans"][0]["spans"], json!([{"name": "GET /"}]));
+    }
+
+    // Request line, Content-Type and body of a request received by the collector
+    type Request = (String, String, Value);
+
+    // Collector answering every request with 200, on a port of its own
+    fn collector() -> (String, mpsc::UnboundedReceiver<Request>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1/traces", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut request_line = String::new();
+                let mut content_type = String::new();
+                let mut length = 0;
+
+                reader.read_line(&mut request_line).unwrap();
+
+                loop {
+                    let mut line = String::new();
+
+                    reader.read_line(&mut line).unwrap();
+
+                    match line.trim().split_once(": ") {
+                        Some((name, value)) if name.eq_ignore_ascii_case("content-length") => length = value.parse().unwrap(),
+                        Some((name, value)) if name.eq_ignore_ascii_case("content-type") => content_type = value.to_string(),
+                        Some(_) => {},
+                        None => break
+                    }
+                }
+
+                let mut body = vec![0; length];
+
+                reader.read_exact(&mut body).unwrap();
+                reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").unwrap();
+
+                let _ = sender.send((request_line.trim().to_string(), content_type, serde_json::from_slice(&body).unwrap()));
+            }
+        });
+
+        (url, receiver)
+    }
+
+    fn span(name: &str) -> Value {
+        Span::child_of(name, Kind::Server, None).to_otlp(UNIX_EPOCH)
+    }
+
+    async fn flush(queue: &mpsc::Sender<Message>) {
+        let (done, flushed) = oneshot::channel();
+
+        queue.send(Message::Flush(done)).await.unwrap();
+
+        timeout(Duration::from_secs(5), flushed).await.unwrap().unwrap();
+    }
+
+    #[actix_web::test]
+    async fn flushing_posts_the_waiting_spans_as_one_document() {
+        let (url, mut requests) = collector();
+        let (queue, receiver) = mpsc::channel(16);
+        let spans = vec![span("GET /dishes"), span("POST /dishes")];
+
+        actix_web::rt::spawn(export(url, String::from("meals"), receiver));
+
+        for span in &spans {
+            queue.send(Message::Span(span.clone())).await.unwrap();
+        }
+
+        flush(&queue).await;
+
+        let (request_line, content_type, body) = requests.recv().await.unwrap();
+
+        assert_eq!(request_line, "POST /v1/traces HTTP/1.1");
+        assert_eq!(content_type, "application/json");
+        assert_eq!(body, document("meals", spans));
+    }
+
+    #[actix_web::test]
+    async fn full_batches_are_posted_right_away() {
+        let (url, mut requests) = collector();
+        let (queue, receiver) = mpsc::channel(BATCH_SIZE);
+
+        actix_web::rt::spawn(export(url, String::from("meals"), receiver));
+
+        for _ in 0..BATCH_SIZE {
+            queue.send(Message::Span(span("GET /"))).await.unwrap();
+        }
+
+        let (_, _, body) = timeout(Duration::from_secs(2), requests.recv()).await.unwrap().unwrap();
+
+        assert_eq!(body["resourceSpans"][0]["scopeSpans"][0]["spans"].as_array().unwrap().len(), BATCH_SIZE);
+    }
+
+    #[actix_web::test]
+    async fn nothing_is_posted_without_spans() {
+        let (url, mut requests) = collector();
+        let (queue, receiver) = mpsc::channel(16);
+
+        actix_web::rt::spawn(export(url, String::from("meals"), receiver));
+
+        flush(&queue).await;
+
+        assert!(timeout(Duration::from_millis(200), requests.recv()).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn flushes_finish_when_the_collector_is_down() {
+        let url = format!("http://{}/v1/traces", TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap());
+        let (queue, receiver) = mpsc::channel(16);
+
+        actix_web::rt::spawn(export(url, String::from("meals"), receiver));
+
+        queue.send(Message::Span(span("GET /"))).await.unwrap();
+
+        flush(&queue).await;
+    }
+}